@@ -0,0 +1,9 @@
+/* The IAP routines in ROM use the top 32 bytes of RAM as scratch space. Include this file from
+   memory.x, after the MEMORY block, to keep the stack out of it:
+
+     INCLUDE iap.x
+*/
+__iap_scratch_start = ORIGIN(RAM) + LENGTH(RAM) - 32;
+_stack_start = __iap_scratch_start;
+
+ASSERT(_stack_start <= __iap_scratch_start, "the stack overlaps the IAP scratch area in the top 32 bytes of RAM");
@@ -1,12 +1,25 @@
-use crate::pac;
+use crate::{
+    gpio::SealedPin,
+    pac, peripherals,
+    rom::power::{self, PowerMode, SetPllError, SetPllMode},
+    usart::BaudDivisors,
+};
 
 use core::{
+    marker::PhantomData,
     num::{NonZeroU8, NonZeroU32},
-    sync::atomic::AtomicU32,
+    sync::atomic::{AtomicU32, Ordering},
+};
+use embassy_hal_internal::{Peri, PeripheralType};
+use pac::{
+    SYSCON,
+    syscon::vals::{MainclkselSel, PllclkselSel, UsbclkselSel},
 };
-use pac::syscon::vals::{MainclkselSel, PllclkselSel, UsbclkselSel};
+
+pub mod irc;
 
 struct Clocks {
+    irc: AtomicU32,
     sysosc: AtomicU32,
     wdosc: AtomicU32,
     sys_pll: AtomicU32,
@@ -16,20 +29,142 @@ struct Clocks {
     ssp0_pclk: AtomicU32,
     ssp1_pclk: AtomicU32,
     usart_pclk: AtomicU32,
+    wwdt_clk: AtomicU32,
+    clkout: AtomicU32,
 }
 
 static CLOCKS: Clocks = Clocks {
+    irc: AtomicU32::new(12_000_000),
     sysosc: AtomicU32::new(0),
     wdosc: AtomicU32::new(0),
     sys_pll: AtomicU32::new(0),
     usb_pll: AtomicU32::new(0),
-    mainclk: AtomicU32::new(0),
+    mainclk: AtomicU32::new(12_000_000),
     usb_pclk: AtomicU32::new(0),
     ssp0_pclk: AtomicU32::new(0),
     ssp1_pclk: AtomicU32::new(0),
     usart_pclk: AtomicU32::new(0),
+    wwdt_clk: AtomicU32::new(12_000_000),
+    clkout: AtomicU32::new(0),
 };
 
+/// Frequency of the IRC, or 0 if it is powered down.
+#[inline]
+pub fn irc_hz() -> u32 {
+    CLOCKS.irc.load(Ordering::Relaxed)
+}
+
+/// Frequency of the system oscillator, or 0 if it is powered down.
+#[inline]
+pub fn sysosc_hz() -> u32 {
+    CLOCKS.sysosc.load(Ordering::Relaxed)
+}
+
+/// Frequency of the watchdog oscillator, or 0 if it is powered down.
+#[inline]
+pub fn wdosc_hz() -> u32 {
+    CLOCKS.wdosc.load(Ordering::Relaxed)
+}
+
+/// Output frequency of the system PLL, or 0 if it is powered down.
+#[inline]
+pub fn syspll_hz() -> u32 {
+    CLOCKS.sys_pll.load(Ordering::Relaxed)
+}
+
+/// Output frequency of the USB PLL, or 0 if it is powered down.
+#[inline]
+pub fn usbpll_hz() -> u32 {
+    CLOCKS.usb_pll.load(Ordering::Relaxed)
+}
+
+/// Frequency of the system clock driving the core, memories and AHB peripherals.
+#[inline]
+pub fn mainclk_hz() -> u32 {
+    CLOCKS.mainclk.load(Ordering::Relaxed)
+}
+
+/// Frequency of the USB clock, or 0 if it is disabled.
+#[inline]
+pub fn usb_clk_hz() -> u32 {
+    CLOCKS.usb_pclk.load(Ordering::Relaxed)
+}
+
+/// Frequency of the SSP0 peripheral clock, or 0 if it is disabled.
+#[inline]
+pub fn ssp0_pclk_hz() -> u32 {
+    CLOCKS.ssp0_pclk.load(Ordering::Relaxed)
+}
+
+/// Frequency of the SSP1 peripheral clock, or 0 if it is disabled.
+#[inline]
+pub fn ssp1_pclk_hz() -> u32 {
+    CLOCKS.ssp1_pclk.load(Ordering::Relaxed)
+}
+
+/// Frequency of the USART peripheral clock, or 0 if it is disabled.
+#[inline]
+pub fn usart_pclk_hz() -> u32 {
+    CLOCKS.usart_pclk.load(Ordering::Relaxed)
+}
+
+/// Frequency of the clock feeding the windowed watchdog, before its fixed divide-by-4.
+#[inline]
+pub fn wwdt_clk_hz() -> u32 {
+    CLOCKS.wwdt_clk.load(Ordering::Relaxed)
+}
+
+/// Incremented every time [`reconfigure`] changes the clock tree.
+static EPOCH: AtomicU32 = AtomicU32::new(0);
+
+/// Current clock epoch.
+///
+/// Drivers that derive dividers from the clock frequencies (e.g. baud rates or SPI prescalers)
+/// compare this against the epoch their dividers were computed in, and recompute them when it
+/// differs. See [`EpochTracker`].
+///
+/// Nothing is notified of a change: the drivers in this crate do not track the epoch yet, so
+/// code computing dividers itself, e.g. with [`BaudDivisors`], must poll it.
+#[inline]
+pub fn epoch() -> u32 {
+    EPOCH.load(Ordering::Acquire)
+}
+
+/// Tracks whether the clock tree changed since the last check.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct EpochTracker {
+    seen: u32,
+}
+
+impl EpochTracker {
+    #[inline]
+    pub fn new() -> Self {
+        Self { seen: epoch() }
+    }
+
+    /// Returns `true` once for every change of the clock tree.
+    #[inline]
+    pub fn changed(&mut self) -> bool {
+        let current = epoch();
+        let changed = current != self.seen;
+        self.seen = current;
+        changed
+    }
+}
+
+impl Default for EpochTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Frequency output on the CLKOUT pin, or 0 if [`ClkOut`] is not active.
+#[inline]
+pub fn clkout_hz() -> u32 {
+    CLOCKS.clkout.load(Ordering::Relaxed)
+}
+
 #[repr(u8)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -48,6 +183,14 @@ pub enum MainClkSrc {
     SysPll = MainclkselSel::PLL_OUT as _,
 }
 
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WwdtClkSrc {
+    Irc = 0,
+    WdOsc = 1,
+}
+
 #[repr(u8)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -56,6 +199,8 @@ pub enum UsbClkSrc {
     UsbPll = UsbclkselSel::USB_PLL_OUT as _,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct ClockConfig {
     pub irc: IrcConfig,
     pub sysosc_khz: Option<NonZeroU32>,
@@ -67,23 +212,103 @@ pub struct ClockConfig {
     pub ssp0_pclk_divider: Option<NonZeroU8>,
     pub ssp1_pclk_divider: Option<NonZeroU8>,
     pub usart_pclk_divider: Option<NonZeroU8>,
+    /// Clock of the windowed watchdog, or `None` to leave it unclocked.
+    pub wwdt_clk: Option<WwdtClkSrc>,
+    /// ROM power profile applied whenever the system clock changes.
+    pub power_mode: PowerMode,
+    /// How the system PLL is programmed when it drives the main clock.
+    pub sys_pll_strategy: PllStrategy,
+}
+
+/// How the system PLL is programmed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PllStrategy {
+    /// Write `sys_pll` and `mainclk` as calculated by this crate.
+    #[default]
+    Crate,
+    /// Let the ROM's `set_pll` routine pick the PLL settings and system clock divider for a
+    /// system clock of `target_khz`, using `sys_pll` only for its input selection.
+    ///
+    /// The frequency the ROM settled on is reported by [`mainclk_hz`].
+    Rom { mode: SetPllMode, target_khz: u32 },
 }
 
+/// Maximum system clock frequency.
+const MAINCLK_MAX_KHZ: u32 = 50_000;
+/// Frequency the USB peripheral must be clocked at.
+const USB_KHZ: u32 = 48_000;
+/// Supported crystal range of the system oscillator.
+const SYSOSC_MIN_KHZ: u32 = 1_000;
+const SYSOSC_MAX_KHZ: u32 = 25_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum ClockError {
     /// Requested system clock out of range
     SysClkOutOfRange,
     /// Requested watchdog oscillator frequency out of range
     WdOscOutOfRange,
+    /// System oscillator frequency is outside of 1-25MHz.
+    SysOscOutOfRange,
     /// Could not find valid PLL parameters for system PLL.
     InvalidSysPllParameters,
     /// Could not find valid PLL parameters for USB PLL.
     InvalidUsbPllParameters,
     /// USB clock is not 48MHz
     UsbClkOutOfRange,
+    /// Requested peripheral clock can not be reached with an 8-bit divider.
+    PeripheralClkOutOfRange,
+    /// The main clock's source is not enabled.
+    MainClkSrcDisabled,
+    /// A PLL's input clock is not enabled.
+    PllSrcDisabled,
+    /// The USB clock's source is not enabled.
+    UsbClkSrcDisabled,
     /// System PLL failed to lock within the timeout period.
     SysPllLockTimedOut,
     /// USB PLL failed to lock within the timeout period.
     UsbPllLockTimedOut,
+    /// The CLKOUT source is not running.
+    ClkOutSrcDisabled,
+    /// The windowed watchdog's clock source is not enabled.
+    WwdtClkSrcDisabled,
+    /// The ROM rejected the power profile for the new system clock.
+    PowerProfileRejected,
+    /// The main clock runs directly from the system oscillator, but the system PLL input is the
+    /// IRC. Both share the SYSPLLCLKSEL selection.
+    PllSrcConflict,
+    /// The IRC was given as the PLL input with a frequency other than 12MHz.
+    IrcInputMismatch,
+}
+
+impl ClockError {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::SysClkOutOfRange => "system clock out of range",
+            Self::WdOscOutOfRange => "watchdog oscillator frequency out of range",
+            Self::SysOscOutOfRange => "system oscillator frequency out of range",
+            Self::InvalidSysPllParameters => "invalid system PLL parameters",
+            Self::InvalidUsbPllParameters => "invalid USB PLL parameters",
+            Self::UsbClkOutOfRange => "USB clock is not 48MHz",
+            Self::PeripheralClkOutOfRange => "peripheral clock out of range",
+            Self::MainClkSrcDisabled => "main clock source is not enabled",
+            Self::PllSrcDisabled => "PLL input clock is not enabled",
+            Self::UsbClkSrcDisabled => "USB clock source is not enabled",
+            Self::SysPllLockTimedOut => "system PLL failed to lock",
+            Self::UsbPllLockTimedOut => "USB PLL failed to lock",
+            Self::ClkOutSrcDisabled => "CLKOUT source is not running",
+            Self::WwdtClkSrcDisabled => "watchdog clock source is not enabled",
+            Self::PowerProfileRejected => "power profile rejected",
+            Self::PllSrcConflict => "main clock and system PLL need different PLL inputs",
+            Self::IrcInputMismatch => "IRC input frequency is not 12MHz",
+        }
+    }
+
+    /// Panic with this error's description. In a `const` context this is a compile error.
+    pub const fn panic(self) -> ! {
+        ::core::panic!("{}", self.as_str())
+    }
 }
 
 impl Default for ClockConfig {
@@ -113,54 +338,122 @@ impl ClockConfig {
             ssp0_pclk_divider: None,
             ssp1_pclk_divider: None,
             usart_pclk_divider: None,
+            wwdt_clk: Some(WwdtClkSrc::Irc),
+            power_mode: PowerMode::Default,
+            sys_pll_strategy: PllStrategy::Crate,
         }
     }
 
     /// Use the IRC to drive the system PLL to 24MHz, and use the system PLL as the main clock's source.
     pub const fn irc_24mhz() -> Self {
-        Self {
-            mainclk: MainClkConfig {
-                source: MainClkSrc::SysPll,
-                divider: NonZeroU8::new(1).unwrap(),
-            },
-            sys_pll: Some(PllConfig {
-                source: PllClkSrc::Irc,
-                m: 2,
-                p: 4,
-            }),
-            ..Self::default()
+        match Self::for_target(PllClkSrc::Irc, 12_000, 24_000, false) {
+            Ok(config) => config,
+            Err(e) => e.panic(),
         }
     }
 
-    /// Use the IRC to drive the system PLL to 24MHz, and use the system PLL as the main clock's source.
+    /// Use the IRC to drive the system PLL to 48MHz, and use the system PLL as the main clock's source.
     pub const fn irc_48mhz() -> Self {
-        Self {
-            mainclk: MainClkConfig {
-                source: MainClkSrc::SysPll,
-                divider: NonZeroU8::new(1).unwrap(),
-            },
-            sys_pll: Some(PllConfig {
-                source: PllClkSrc::Irc,
-                m: 4,
-                p: 2,
-            }),
-            ..Self::default()
+        match Self::for_target(PllClkSrc::Irc, 12_000, 48_000, false) {
+            Ok(config) => config,
+            Err(e) => e.panic(),
         }
     }
 
     /// Use an external cystal oscillator as the main clock source.
+    ///
+    /// The IRC is powered down, so the windowed watchdog is left unclocked.
     pub const fn crystal_oscillator(khz: u32) -> Self {
         Self {
             irc: IrcConfig::Disabled,
-            sysosc_khz: Some(NonZeroU32::new(khz).expect("hz must be non-zero")),
+            sysosc_khz: NonZeroU32::new(khz),
             mainclk: MainClkConfig {
                 source: MainClkSrc::SysOsc,
                 divider: NonZeroU8::new(1).unwrap(),
             },
+            wwdt_clk: None,
             ..Self::default()
         }
     }
 
+    /// Search the system PLL multiplier and post divider, the main clock divider and the USB PLL
+    /// for the valid configuration with a main clock closest to `mainclk_target_khz`.
+    ///
+    /// `input_khz` is the frequency of the system oscillator, and must be 12000 when `source` is
+    /// the IRC, or [`ClockError::IrcInputMismatch`] is returned. When `usb` is set, the USB clock
+    /// is also configured for full speed operation, which requires the system oscillator.
+    ///
+    /// The achieved frequency is reported by [`Self::mainclk_khz`] on the returned configuration.
+    pub const fn for_target(
+        source: PllClkSrc,
+        input_khz: u32,
+        mainclk_target_khz: u32,
+        usb: bool,
+    ) -> Result<Self, ClockError> {
+        let mut config = match source {
+            PllClkSrc::Irc if input_khz != 12_000 => return Err(ClockError::IrcInputMismatch),
+            PllClkSrc::Irc => Self::irc_12mhz(),
+            PllClkSrc::Sysosc => Self::crystal_oscillator(input_khz),
+        };
+        let Some(input_khz) = config.pll_input_khz(source) else {
+            return Err(ClockError::PllSrcDisabled);
+        };
+        if mainclk_target_khz == 0 || mainclk_target_khz > MAINCLK_MAX_KHZ {
+            return Err(ClockError::SysClkOutOfRange);
+        }
+
+        let direct_source = match source {
+            PllClkSrc::Irc => MainClkSrc::Irc,
+            PllClkSrc::Sysosc => MainClkSrc::SysOsc,
+        };
+        let mut best = MainClkCandidate::new(
+            direct_source,
+            None,
+            input_khz,
+            mainclk_target_khz,
+            usb && usb_from_mainclk(source, input_khz),
+        );
+
+        let mut m = 1u8;
+        while m <= 32 {
+            if let Some(pll) = PllConfig::with_multiplier(source, input_khz, m) {
+                let output_khz = input_khz * m as u32;
+                if let Some(candidate) = MainClkCandidate::new(
+                    MainClkSrc::SysPll,
+                    Some(pll),
+                    output_khz,
+                    mainclk_target_khz,
+                    usb && usb_from_mainclk(source, output_khz),
+                ) && candidate.is_better_than(&best)
+                {
+                    best = Some(candidate);
+                }
+            }
+            m += 1;
+        }
+
+        let Some(best) = best else {
+            return Err(ClockError::SysClkOutOfRange);
+        };
+        config.mainclk = MainClkConfig {
+            source: best.source,
+            divider: best.divider,
+        };
+        config.sys_pll = best.pll;
+
+        if usb {
+            config = match config.enable_usb_fs() {
+                Ok(config) => config,
+                Err(e) => return Err(e),
+            };
+        }
+
+        match config.validate() {
+            Ok(()) => Ok(config),
+            Err(e) => Err(e),
+        }
+    }
+
     #[inline]
     pub const fn irc_khz(&self) -> Option<u32> {
         match self.irc {
@@ -179,33 +472,62 @@ impl ClockConfig {
 
     #[inline]
     pub const fn wdosc_khz(&self) -> Option<u32> {
-        ::core::todo!()
+        match self.wdosc {
+            None => None,
+            Some(wdosc) => Some(wdosc.hz() / 1_000),
+        }
+    }
+
+    #[inline]
+    pub const fn wdosc_hz(&self) -> Option<u32> {
+        match self.wdosc {
+            None => None,
+            Some(wdosc) => Some(wdosc.hz()),
+        }
     }
 
+    #[inline]
+    const fn pll_input_khz(&self, source: PllClkSrc) -> Option<u32> {
+        match source {
+            PllClkSrc::Irc => self.irc_khz(),
+            PllClkSrc::Sysosc => self.sysosc_khz(),
+        }
+    }
+
+    /// Output frequency of the system PLL, or `None` if it is not configured or its input is not
+    /// running.
     #[inline]
     pub const fn syspll_khz(&self) -> Option<u32> {
-        match self.sys_pll {
+        match &self.sys_pll {
             None => None,
-            Some(PllConfig {
-                source: PllClkSrc::Irc,
-                m,
-                p: _,
-            }) => Some(self.irc_khz().expect("irc must be enabled") * m as u32),
-            Some(PllConfig {
-                source: PllClkSrc::Sysosc,
-                m,
-                p: _,
-            }) => Some(self.sysosc_khz().expect("sysosc_khz must be set") * m as u32),
+            Some(pll) => match self.pll_input_khz(pll.source) {
+                None => None,
+                Some(input_khz) => Some(input_khz * pll.m as u32),
+            },
         }
     }
 
+    /// Frequency of the main clock, before the system clock divider.
     #[inline]
-    pub const fn mainclk_src_khz(&self) -> u32 {
-        match self.mainclk.source {
-            MainClkSrc::Irc => self.irc_khz().expect("irc must be enabled"),
-            MainClkSrc::SysOsc => self.sysosc_khz().expect("sysosc_khz must be set"),
-            MainClkSrc::SysPll => self.syspll_khz().expect("system pll must be configured"),
-            MainClkSrc::WdOsc => ::core::todo!(),
+    pub const fn mainclk_src_khz(&self) -> Result<u32, ClockError> {
+        let khz = match self.mainclk.source {
+            MainClkSrc::Irc => self.irc_khz(),
+            MainClkSrc::SysOsc => self.sysosc_khz(),
+            MainClkSrc::SysPll => self.syspll_khz(),
+            MainClkSrc::WdOsc => self.wdosc_khz(),
+        };
+
+        match khz {
+            Some(khz) => Ok(khz),
+            None => Err(ClockError::MainClkSrcDisabled),
+        }
+    }
+
+    #[inline]
+    const fn mainclk_src_khz_opt(&self) -> Option<u32> {
+        match self.mainclk_src_khz() {
+            Ok(khz) => Some(khz),
+            Err(_) => None,
         }
     }
 
@@ -224,115 +546,385 @@ impl ClockConfig {
         }
     }
 
+    /// Frequency of the system clock, after the system clock divider.
     #[inline]
-    pub const fn mainclk_khz(&self) -> u32 {
-        self.mainclk_src_khz() / (self.mainclk.divider.get() as u32)
+    pub const fn mainclk_khz(&self) -> Result<u32, ClockError> {
+        match self.mainclk_src_khz() {
+            Ok(khz) => Ok(khz / (self.mainclk.divider.get() as u32)),
+            Err(e) => Err(e),
+        }
     }
 
+    /// Like [`Self::mainclk_khz`], but without losing the sub-kHz part of a watchdog oscillator
+    /// sourced main clock.
+    #[inline]
+    pub const fn mainclk_hz(&self) -> Result<u32, ClockError> {
+        let src_hz = match (self.mainclk.source, self.wdosc_hz(), self.mainclk_src_khz()) {
+            (MainClkSrc::WdOsc, Some(hz), _) => hz,
+            (_, _, Ok(khz)) => khz * 1_000,
+            (_, _, Err(e)) => return Err(e),
+        };
+        Ok(src_hz / (self.mainclk.divider.get() as u32))
+    }
+
+    #[inline]
+    pub const fn wwdt_clk_hz(&self) -> Option<u32> {
+        match self.wwdt_clk {
+            None => None,
+            Some(WwdtClkSrc::Irc) => match self.irc_khz() {
+                None => None,
+                Some(khz) => Some(khz * 1_000),
+            },
+            Some(WwdtClkSrc::WdOsc) => self.wdosc_hz(),
+        }
+    }
+
+    /// Run the main clock from the watchdog oscillator, as close to `target_hz` as possible.
+    ///
+    /// The watchdog oscillator is also selected as the windowed watchdog's clock.
+    #[inline]
+    pub const fn wdosc(target_hz: u32) -> Option<Self> {
+        let Some(wdosc) = WdOscConfig::calculate(target_hz) else {
+            return None;
+        };
+
+        Some(Self {
+            irc: IrcConfig::Disabled,
+            wdosc: Some(wdosc),
+            mainclk: MainClkConfig {
+                source: MainClkSrc::WdOsc,
+                divider: NonZeroU8::new(1).unwrap(),
+            },
+            wwdt_clk: Some(WwdtClkSrc::WdOsc),
+            ..Self::default()
+        })
+    }
+
+    /// Output frequency of the USB PLL, or `None` if it is not configured or its input is not
+    /// running.
     #[inline]
     pub const fn usbpll_khz(&self) -> Option<u32> {
-        match self.usb_pll {
+        match &self.usb_pll {
             None => None,
-            Some(PllConfig {
-                source: PllClkSrc::Irc,
-                m,
-                p: _,
-            }) => Some(self.irc_khz().expect("irc must be enabled") * m as u32),
-            Some(PllConfig {
-                source: PllClkSrc::Sysosc,
-                m,
-                p: _,
-            }) => Some(self.sysosc_khz().expect("sysosc_khz must be set") * m as u32),
+            Some(pll) => match self.pll_input_khz(pll.source) {
+                None => None,
+                Some(input_khz) => Some(input_khz * pll.m as u32),
+            },
         }
     }
 
     #[inline]
     pub const fn usbclk_khz(&self) -> Option<u32> {
-        match self.usb_pclk {
-            None => None,
+        let (src_khz, divider) = match self.usb_pclk {
+            None => return None,
             Some(UsbClkConfig {
                 source: UsbClkSrc::MainClk,
                 divider,
-            }) => Some(self.mainclk_khz() / (divider.get() as u32)),
+            }) => (self.mainclk_src_khz_opt(), divider),
             Some(UsbClkConfig {
                 source: UsbClkSrc::UsbPll,
                 divider,
-            }) => Some(
-                self.usbpll_khz().expect("usb pll must be configured") / (divider.get() as u32),
-            ),
+            }) => (self.usbpll_khz(), divider),
+        };
+
+        match src_khz {
+            None => None,
+            Some(khz) => Some(khz / (divider.get() as u32)),
         }
     }
 
+    /// Peripheral clock dividers are fed by the main clock, before the system clock divider.
     #[inline]
-    pub const fn ssp0_pclk_khz(&self) -> Option<u32> {
-        match self.ssp0_pclk_divider {
-            None => None,
-            Some(divider) => Some(self.mainclk_khz() / (divider.get() as u32)),
+    const fn pclk_khz(&self, divider: Option<NonZeroU8>) -> Option<u32> {
+        match (divider, self.mainclk_src_khz()) {
+            (Some(divider), Ok(khz)) => Some(khz / (divider.get() as u32)),
+            _ => None,
         }
     }
 
+    #[inline]
+    pub const fn ssp0_pclk_khz(&self) -> Option<u32> {
+        self.pclk_khz(self.ssp0_pclk_divider)
+    }
+
     #[inline]
     pub const fn ssp1_pclk_khz(&self) -> Option<u32> {
-        match self.ssp1_pclk_divider {
-            None => None,
-            Some(divider) => Some(self.mainclk_khz() / (divider.get() as u32)),
-        }
+        self.pclk_khz(self.ssp1_pclk_divider)
     }
 
     #[inline]
     pub const fn usart_pclk_khz(&self) -> Option<u32> {
-        match self.usart_pclk_divider {
-            None => None,
-            Some(divider) => Some(self.mainclk_khz() / (divider.get() as u32)),
-        }
+        self.pclk_khz(self.usart_pclk_divider)
+    }
+
+    /// Program the system PLL through the ROM's `set_pll` routine for a `target_khz` system clock
+    /// instead of using the crate-calculated settings. Only the input selection of `sys_pll` is
+    /// used.
+    #[inline]
+    pub const fn with_rom_pll(mut self, mode: SetPllMode, target_khz: u32) -> Self {
+        self.sys_pll_strategy = PllStrategy::Rom { mode, target_khz };
+        self
     }
 
+    /// Whether the ROM programs the system PLL driving the main clock.
     #[inline]
-    pub const fn enable_usb_fs(mut self) -> Self {
-        // We need to target 48MHz
-        const TARGET_KHZ: u32 = 48_000;
+    const fn uses_rom_pll(&self) -> bool {
+        matches!(self.sys_pll_strategy, PllStrategy::Rom { .. })
+            && matches!(self.mainclk.source, MainClkSrc::SysPll)
+    }
+
+    /// Check this configuration against the constraints in the user manual.
+    ///
+    /// When evaluated in a `const` (see [`Self::validated`]), an invalid configuration fails to
+    /// compile.
+    pub const fn validate(&self) -> Result<(), ClockError> {
+        if let Some(khz) = self.sysosc_khz()
+            && (khz < SYSOSC_MIN_KHZ || khz > SYSOSC_MAX_KHZ)
+        {
+            return Err(ClockError::SysOscOutOfRange);
+        }
+
+        if let Some(wdosc) = &self.wdosc
+            && wdosc.divsel > WdOscConfig::MAX_DIVSEL
+        {
+            return Err(ClockError::WdOscOutOfRange);
+        }
+
+        // The ROM solves the multiplier and divider itself.
+        if let Some(pll) = &self.sys_pll {
+            match self.pll_input_khz(pll.source) {
+                None => return Err(ClockError::PllSrcDisabled),
+                Some(input_khz) if !self.uses_rom_pll() && !pll.is_valid(input_khz) => {
+                    return Err(ClockError::InvalidSysPllParameters);
+                }
+                Some(_) => {}
+            }
+        }
+
+        if let Some(pll) = &self.usb_pll {
+            match self.pll_input_khz(pll.source) {
+                None => return Err(ClockError::PllSrcDisabled),
+                Some(input_khz) if !pll.is_valid(input_khz) => {
+                    return Err(ClockError::InvalidUsbPllParameters);
+                }
+                Some(_) => {}
+            }
+        }
+
+        // The system oscillator reaches the main clock through the PLL input selection.
+        if matches!(self.mainclk.source, MainClkSrc::SysOsc)
+            && matches!(
+                self.sys_pll,
+                Some(PllConfig {
+                    source: PllClkSrc::Irc,
+                    ..
+                })
+            )
+        {
+            return Err(ClockError::PllSrcConflict);
+        }
+
+        match (self.sys_pll_strategy, self.uses_rom_pll()) {
+            (PllStrategy::Rom { target_khz, .. }, true) => {
+                if self.sys_pll.is_none() {
+                    return Err(ClockError::MainClkSrcDisabled);
+                }
+                if target_khz == 0 || target_khz > MAINCLK_MAX_KHZ {
+                    return Err(ClockError::SysClkOutOfRange);
+                }
+            }
+            _ => match self.mainclk_khz() {
+                Err(e) => return Err(e),
+                Ok(khz) if khz > MAINCLK_MAX_KHZ => return Err(ClockError::SysClkOutOfRange),
+                Ok(_) => {}
+            },
+        }
+
+        if self.wwdt_clk.is_some() && self.wwdt_clk_hz().is_none() {
+            return Err(ClockError::WwdtClkSrcDisabled);
+        }
+
+        // The ROM picks the main clock before the system clock divider, so it can not be relied on
+        // to feed the USB clock.
+        if self.uses_rom_pll()
+            && matches!(
+                self.usb_pclk,
+                Some(UsbClkConfig {
+                    source: UsbClkSrc::MainClk,
+                    ..
+                })
+            )
+        {
+            return Err(ClockError::UsbClkOutOfRange);
+        }
 
+        if let Some(usb) = &self.usb_pclk {
+            let src_khz = match usb.source {
+                UsbClkSrc::MainClk => self.mainclk_src_khz_opt(),
+                UsbClkSrc::UsbPll => self.usbpll_khz(),
+            };
+            match src_khz {
+                None => return Err(ClockError::UsbClkSrcDisabled),
+                Some(khz) if khz != USB_KHZ * usb.divider.get() as u32 => {
+                    return Err(ClockError::UsbClkOutOfRange);
+                }
+                Some(_) => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns `self` if it is valid, and panics otherwise.
+    ///
+    /// Intended for `const` items, where the panic becomes a compile error:
+    /// ```rust,ignore
+    /// const CLOCKS: ClockConfig = ClockConfig::crystal_oscillator(12_000).validated();
+    /// ```
+    pub const fn validated(self) -> Self {
+        if let Err(e) = self.validate() {
+            e.panic();
+        }
+        self
+    }
+
+    /// Clock the USB peripheral from a 48MHz clock derived from the system oscillator, either
+    /// through the main clock or the USB PLL.
+    #[inline]
+    pub const fn enable_usb_fs(mut self) -> Result<Self, ClockError> {
         // First, check mainclk_src, since then we wouldn't need the USB PLL
-        let mainclk_src_khz = self.mainclk_src_khz();
-        let (quot, rem) = (TARGET_KHZ / mainclk_src_khz, TARGET_KHZ % mainclk_src_khz);
-        if self.mainclk_is_sysosc_sourced() && rem == 0 && quot < 256 {
-            self.usb_pll = None;
-            self.usb_pclk = Some(UsbClkConfig {
-                divider: NonZeroU8::new(quot as u8).unwrap(),
-                source: UsbClkSrc::MainClk,
-            });
-            return self;
+        if self.mainclk_is_sysosc_sourced()
+            && let Ok(mainclk_src_khz) = self.mainclk_src_khz()
+        {
+            let (quot, rem) = (mainclk_src_khz / USB_KHZ, mainclk_src_khz % USB_KHZ);
+            if rem == 0 && quot > 0 && quot < 256 {
+                self.usb_pll = None;
+                self.usb_pclk = Some(UsbClkConfig {
+                    divider: NonZeroU8::new(quot as u8).unwrap(),
+                    source: UsbClkSrc::MainClk,
+                });
+                return Ok(self);
+            }
         }
 
         // Next, check if we have a system oscillator
-        if let Some(sysosc_khz) = self.sysosc_khz()
-            && let Some((settings, divider)) =
-                PllConfig::calculate_with_divider(PllClkSrc::Sysosc, sysosc_khz, TARGET_KHZ)
-        {
-            self.usb_pll = Some(settings);
-            self.usb_pclk = Some(UsbClkConfig {
-                divider: NonZeroU8::new(divider).unwrap(),
-                source: UsbClkSrc::UsbPll,
-            });
-            return self;
+        let Some(sysosc_khz) = self.sysosc_khz() else {
+            return Err(ClockError::UsbClkSrcDisabled);
+        };
+        let Some((settings, divider)) =
+            PllConfig::calculate_with_divider(PllClkSrc::Sysosc, sysosc_khz, USB_KHZ)
+        else {
+            return Err(ClockError::InvalidUsbPllParameters);
+        };
+
+        self.usb_pll = Some(settings);
+        self.usb_pclk = Some(UsbClkConfig {
+            divider: NonZeroU8::new(divider).unwrap(),
+            source: UsbClkSrc::UsbPll,
+        });
+        Ok(self)
+    }
+
+    /// Clock SSP0 at no more than `target_khz`.
+    pub const fn enable_ssp0(mut self, target_khz: u32) -> Result<Self, ClockError> {
+        match pclk_divider(self.mainclk_src_khz(), target_khz) {
+            Ok(divider) => {
+                self.ssp0_pclk_divider = Some(divider);
+                Ok(self)
+            }
+            Err(e) => Err(e),
         }
+    }
 
-        ::core::panic!("Could not determine clock parameters for full speed USB operation. Either a system oscillator is not configured, or it can not be converted to 48MHz.")
+    /// Clock SSP1 at no more than `target_khz`.
+    pub const fn enable_ssp1(mut self, target_khz: u32) -> Result<Self, ClockError> {
+        match pclk_divider(self.mainclk_src_khz(), target_khz) {
+            Ok(divider) => {
+                self.ssp1_pclk_divider = Some(divider);
+                Ok(self)
+            }
+            Err(e) => Err(e),
+        }
     }
 
-    pub const fn enable_ssp0(mut self, target_khz: u32) -> Self {
-        let mainclk_khz = self.mainclk_khz();
-        if mainclk_khz < target_khz {
-            ::core::panic!("SSP0 out of range");
+    /// Clock the USART at no more than `target_khz`.
+    pub const fn enable_usart_khz(mut self, target_khz: u32) -> Result<Self, ClockError> {
+        match pclk_divider(self.mainclk_src_khz(), target_khz) {
+            Ok(divider) => {
+                self.usart_pclk_divider = Some(divider);
+                Ok(self)
+            }
+            Err(e) => Err(e),
         }
+    }
 
-        let divider = self.mainclk_khz() / target_khz;
-        self.ssp0_pclk_divider = Some(NonZeroU8::new(divider as u8).unwrap());
-        self
+    /// Clock the USART with the divider that, combined with the fractional baud rate generator,
+    /// gives the smallest error for `baud`.
+    ///
+    /// The baud rate generator settings for the chosen clock are given by
+    /// [`BaudDivisors::calculate`] with [`Self::usart_pclk_khz`].
+    pub const fn enable_usart(mut self, baud: u32) -> Result<Self, ClockError> {
+        let mainclk_src_hz = match self.mainclk_src_khz() {
+            Ok(khz) => khz * 1_000,
+            Err(e) => return Err(e),
+        };
+
+        let mut best: Option<(NonZeroU8, u32)> = None;
+        let mut divider = 1u32;
+        // The USART oversamples by 16, so slower clocks can not reach `baud` at all.
+        while divider <= u8::MAX as u32 && mainclk_src_hz / divider >= 16 * baud {
+            if let Some((_, error)) = BaudDivisors::calculate(mainclk_src_hz / divider, baud) {
+                let better = match best {
+                    None => true,
+                    Some((_, best_error)) => error < best_error,
+                };
+                if better {
+                    best = Some((NonZeroU8::new(divider as u8).unwrap(), error));
+                }
+                if error == 0 {
+                    break;
+                }
+            }
+            divider += 1;
+        }
+
+        match best {
+            Some((divider, _)) => {
+                self.usart_pclk_divider = Some(divider);
+                Ok(self)
+            }
+            None => Err(ClockError::PeripheralClkOutOfRange),
+        }
     }
 }
 
-#[derive(PartialEq, Eq)]
+/// Smallest divider that brings `mainclk_src_khz` down to at most `target_khz`.
+#[inline]
+const fn pclk_divider(
+    mainclk_src_khz: Result<u32, ClockError>,
+    target_khz: u32,
+) -> Result<NonZeroU8, ClockError> {
+    let mainclk_src_khz = match mainclk_src_khz {
+        Ok(khz) => khz,
+        Err(e) => return Err(e),
+    };
+    if target_khz == 0 {
+        return Err(ClockError::PeripheralClkOutOfRange);
+    }
+
+    let divider = mainclk_src_khz.div_ceil(target_khz);
+    if divider > u8::MAX as u32 {
+        return Err(ClockError::PeripheralClkOutOfRange);
+    }
+
+    match NonZeroU8::new(divider as u8) {
+        Some(divider) => Ok(divider),
+        None => Err(ClockError::PeripheralClkOutOfRange),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum IrcConfig {
     Disabled,
     Enabled,
@@ -342,18 +934,162 @@ pub struct SysoscConfig {
     pub frequency: u32,
 }
 
+/// Analog output frequency of the watchdog oscillator (FREQSEL).
+///
+/// The user manual gives these frequencies with a +/- 40% tolerance.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WdOscFreq {
+    Khz600 = 0x1,
+    Khz1050 = 0x2,
+    Khz1400 = 0x3,
+    Khz1750 = 0x4,
+    Khz2100 = 0x5,
+    Khz2400 = 0x6,
+    Khz2700 = 0x7,
+    Khz3000 = 0x8,
+    Khz3250 = 0x9,
+    Khz3500 = 0xA,
+    Khz3750 = 0xB,
+    Khz4000 = 0xC,
+    Khz4200 = 0xD,
+    Khz4400 = 0xE,
+    Khz4600 = 0xF,
+}
+
+impl WdOscFreq {
+    pub const ALL: [Self; 15] = [
+        Self::Khz600,
+        Self::Khz1050,
+        Self::Khz1400,
+        Self::Khz1750,
+        Self::Khz2100,
+        Self::Khz2400,
+        Self::Khz2700,
+        Self::Khz3000,
+        Self::Khz3250,
+        Self::Khz3500,
+        Self::Khz3750,
+        Self::Khz4000,
+        Self::Khz4200,
+        Self::Khz4400,
+        Self::Khz4600,
+    ];
+
+    #[inline]
+    pub const fn khz(self) -> u32 {
+        match self {
+            Self::Khz600 => 600,
+            Self::Khz1050 => 1_050,
+            Self::Khz1400 => 1_400,
+            Self::Khz1750 => 1_750,
+            Self::Khz2100 => 2_100,
+            Self::Khz2400 => 2_400,
+            Self::Khz2700 => 2_700,
+            Self::Khz3000 => 3_000,
+            Self::Khz3250 => 3_250,
+            Self::Khz3500 => 3_500,
+            Self::Khz3750 => 3_750,
+            Self::Khz4000 => 4_000,
+            Self::Khz4200 => 4_200,
+            Self::Khz4400 => 4_400,
+            Self::Khz4600 => 4_600,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct WdOscConfig {
-    pub divider: NonZeroU8,
-    pub analog_clock: u32,
+    pub analog_clock: WdOscFreq,
+    /// The analog clock is divided by `2 * (1 + divsel)`. Valid values are 0 to 31.
+    pub divsel: u8,
+}
+
+impl WdOscConfig {
+    pub const MAX_DIVSEL: u8 = 31;
+    /// Lowest reachable frequency: 600kHz / 64
+    pub const MIN_HZ: u32 = 9_375;
+    /// Highest reachable frequency: 4.6MHz / 2
+    pub const MAX_HZ: u32 = 2_300_000;
+
+    #[inline]
+    pub const fn hz(&self) -> u32 {
+        self.analog_clock.khz() * 1_000 / (2 * (1 + self.divsel as u32))
+    }
+
+    /// Find the FREQSEL/DIVSEL pair closest to `target_hz`.
+    ///
+    /// Returns `None` if `target_hz` is outside of [`Self::MIN_HZ`]..=[`Self::MAX_HZ`].
+    #[inline]
+    pub const fn calculate(target_hz: u32) -> Option<Self> {
+        if target_hz < Self::MIN_HZ || target_hz > Self::MAX_HZ {
+            return None;
+        }
+
+        let mut best = Self {
+            analog_clock: WdOscFreq::Khz600,
+            divsel: 0,
+        };
+        let mut best_error = u32::MAX;
+
+        let mut i = 0;
+        while i < WdOscFreq::ALL.len() {
+            let mut divsel = 0;
+            while divsel <= Self::MAX_DIVSEL {
+                let candidate = Self {
+                    analog_clock: WdOscFreq::ALL[i],
+                    divsel,
+                };
+                let error = candidate.hz().abs_diff(target_hz);
+                if error < best_error {
+                    best = candidate;
+                    best_error = error;
+                }
+                divsel += 1;
+            }
+            i += 1;
+        }
+
+        Some(best)
+    }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct PllConfig {
     pub source: PllClkSrc,
     pub m: u8,
     pub p: u8,
 }
 
+/// User manual: 10MHz <= F_CLKIN <= 25MHz
+const PLL_INPUT_MIN_KHZ: u32 = 10_000;
+const PLL_INPUT_MAX_KHZ: u32 = 25_000;
+/// User manual: F_CLKOUT < 100MHz
+const PLL_OUTPUT_MAX_KHZ: u32 = 100_000;
+/// User manual: 156MHz <= F_CCO <= 320MHz
+const PLL_CCO_MIN_KHZ: u32 = 156_000;
+const PLL_CCO_MAX_KHZ: u32 = 320_000;
+
 impl PllConfig {
+    /// Check the multiplier, post divider, input, output and CCO frequencies against the user
+    /// manual.
+    #[inline]
+    pub const fn is_valid(&self, input_khz: u32) -> bool {
+        if self.m == 0 || self.m > 32 || !matches!(self.p, 1 | 2 | 4 | 8) {
+            return false;
+        }
+        if input_khz < PLL_INPUT_MIN_KHZ || input_khz > PLL_INPUT_MAX_KHZ {
+            return false;
+        }
+
+        let output_khz = input_khz * self.m as u32;
+        let cco_khz = 2 * self.p as u32 * output_khz;
+        output_khz < PLL_OUTPUT_MAX_KHZ && PLL_CCO_MIN_KHZ <= cco_khz && cco_khz <= PLL_CCO_MAX_KHZ
+    }
+
     #[inline]
     pub const fn calculate(source: PllClkSrc, input_khz: u32, target_khz: u32) -> Option<Self> {
         if input_khz > target_khz {
@@ -367,10 +1103,9 @@ impl PllConfig {
         }
 
         let mut p = 1u8;
-        // User manual: 156MHz <= F_CCO <= 320MHz
         while p <= 8 {
             let cco = 2 * (p as u32) * target_khz;
-            if 156_000 <= cco && cco <= 320_000 {
+            if PLL_CCO_MIN_KHZ <= cco && cco <= PLL_CCO_MAX_KHZ {
                 return Some(Self { source, m, p });
             }
             p <<= 1;
@@ -385,24 +1120,125 @@ impl PllConfig {
         input_khz: u32,
         target_khz: u32,
     ) -> Option<(Self, u8)> {
-        let mut div = 1u8;
+        if target_khz == 0 {
+            return None;
+        }
 
-        loop {
-            let target_khz = target_khz * div as u32;
-            if input_khz <= target_khz
-                && let Some(settings) = Self::calculate(source, input_khz, target_khz)
+        // The divider is fixed by the multiplier, so only the 32 multipliers need to be tried.
+        let mut m = 1u8;
+        while m <= 32 {
+            let output_khz = input_khz * m as u32;
+            let div = output_khz / target_khz;
+            if output_khz.is_multiple_of(target_khz)
+                && 0 < div
+                && div <= u8::MAX as u32
+                && let Some(settings) = Self::with_multiplier(source, input_khz, m)
             {
-                return Some((settings, div));
+                return Some((settings, div as u8));
+            }
+            m += 1;
+        }
+
+        None
+    }
+
+    /// Find a post divider that makes `m` valid for `input_khz`.
+    #[inline]
+    const fn with_multiplier(source: PllClkSrc, input_khz: u32, m: u8) -> Option<Self> {
+        let mut p = 1u8;
+        while p <= 8 {
+            let settings = Self { source, m, p };
+            if settings.is_valid(input_khz) {
+                return Some(settings);
+            }
+            p <<= 1;
+        }
+
+        None
+    }
+}
+
+/// Whether a main clock of `src_khz` derived from `source` can also clock the USB peripheral.
+#[inline]
+const fn usb_from_mainclk(source: PllClkSrc, src_khz: u32) -> bool {
+    matches!(source, PllClkSrc::Sysosc)
+        && src_khz.is_multiple_of(USB_KHZ)
+        && src_khz / USB_KHZ <= u8::MAX as u32
+}
+
+/// A main clock setting considered by [`ClockConfig::for_target`].
+#[derive(Clone, Copy)]
+struct MainClkCandidate {
+    source: MainClkSrc,
+    pll: Option<PllConfig>,
+    divider: NonZeroU8,
+    error_hz: u32,
+    /// The USB clock can be taken from the main clock, so the USB PLL can stay off.
+    usb_from_mainclk: bool,
+}
+
+impl MainClkCandidate {
+    /// Pick the divider that brings `src_khz` closest to `target_khz` without exceeding the
+    /// maximum system clock.
+    #[inline]
+    const fn new(
+        source: MainClkSrc,
+        pll: Option<PllConfig>,
+        src_khz: u32,
+        target_khz: u32,
+        usb_from_mainclk: bool,
+    ) -> Option<Self> {
+        let floor = src_khz / target_khz;
+        let candidates = [floor, floor + 1];
+
+        let mut best: Option<Self> = None;
+        let mut i = 0;
+        while i < candidates.len() {
+            let divider = candidates[i];
+            i += 1;
+
+            if divider == 0 || divider > u8::MAX as u32 {
+                continue;
+            }
+            let achieved_hz = src_khz * 1_000 / divider;
+            if achieved_hz > MAINCLK_MAX_KHZ * 1_000 {
+                continue;
             }
 
-            let Some(new_div) = div.checked_add(1) else {
-                return None;
+            let candidate = Self {
+                source,
+                pll,
+                divider: NonZeroU8::new(divider as u8).unwrap(),
+                error_hz: achieved_hz.abs_diff(target_khz * 1_000),
+                usb_from_mainclk,
             };
-            div = new_div
+            if candidate.is_better_than(&best) {
+                best = Some(candidate);
+            }
         }
+
+        best
+    }
+
+    /// Prefer the smallest error, then not needing the USB PLL, then not needing the system PLL.
+    #[inline]
+    const fn is_better_than(&self, other: &Option<Self>) -> bool {
+        let Some(other) = other else {
+            return true;
+        };
+
+        if self.error_hz != other.error_hz {
+            return self.error_hz < other.error_hz;
+        }
+        if self.usb_from_mainclk != other.usb_from_mainclk {
+            return self.usb_from_mainclk;
+        }
+        self.pll.is_none() && other.pll.is_some()
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct MainClkConfig {
     pub source: MainClkSrc,
     pub divider: NonZeroU8,
@@ -417,7 +1253,616 @@ impl Default for MainClkConfig {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct UsbClkConfig {
     pub divider: NonZeroU8,
     pub source: UsbClkSrc,
 }
+
+/// Number of times the PLL status register is polled before giving up on lock.
+const PLL_LOCK_TIMEOUT: u32 = 100_000;
+
+/// Apply `config` to SYSCON.
+///
+/// The main clock is parked on the IRC while the oscillators and PLLs are reprogrammed, and only
+/// switched to its configured source once that source is running. Oscillators and PLLs that are
+/// not part of `config` are powered down afterwards.
+///
+/// Flash wait states (FLASHCFG) and the ROM power profile are adjusted to the new system clock
+/// while the main clock is parked on the IRC.
+///
+/// If a PLL fails to lock or the ROM rejects a setting, the main clock is left running from the
+/// IRC, the USB and peripheral clocks are gated, and the reported frequencies reflect that.
+///
+/// Use [`reconfigure`] to change the clock tree once drivers are running.
+///
+/// # Safety
+/// Must not be called while any peripheral depending on the clock tree is in use.
+pub unsafe fn init(config: ClockConfig) -> Result<(), ClockError> {
+    config.validate()?;
+    unsafe { switch(&config) }
+}
+
+/// Apply an already validated `config`, falling back to the IRC on failure.
+unsafe fn switch(config: &ClockConfig) -> Result<(), ClockError> {
+    let result = unsafe { apply(config) };
+    if result.is_err() {
+        fall_back_to_irc(config);
+    }
+    update_clkout_hz();
+    result
+}
+
+/// Park the main clock on the IRC after a failed [`apply`], and gate every clock derived from it.
+fn fall_back_to_irc(config: &ClockConfig) {
+    SYSCON.pdruncfg().modify(|w| {
+        w.set_ircout_pd(false);
+        w.set_irc_pd(false);
+    });
+    select_mainclk(MainClkSrc::Irc);
+    SYSCON.sysahbclkdiv().write(|w| w.set_div(1));
+    set_flash_wait_states(flash_wait_states(12_000_000));
+    let _ = set_power_profile(config.power_mode, 12_000_000);
+
+    SYSCON.usbclkdiv().write(|w| w.set_div(0));
+    SYSCON.ssp0clkdiv().write(|w| w.set_div(0));
+    SYSCON.ssp1clkdiv().write(|w| w.set_div(0));
+    SYSCON.uartclkdiv().write(|w| w.set_div(0));
+
+    let pdruncfg = SYSCON.pdruncfg().read();
+    let syspll_running = !pdruncfg.syspll_pd() && SYSCON.syspllstat().read().lock();
+    let usbpll_running = !pdruncfg.usbpll_pd() && SYSCON.usbpllstat().read().lock();
+
+    CLOCKS.irc.store(12_000_000, Ordering::Relaxed);
+    CLOCKS
+        .sysosc
+        .store(config.sysosc_khz().unwrap_or(0) * 1_000, Ordering::Relaxed);
+    CLOCKS
+        .wdosc
+        .store(config.wdosc_hz().unwrap_or(0), Ordering::Relaxed);
+    CLOCKS.sys_pll.store(
+        match syspll_running {
+            true => config.syspll_khz().unwrap_or(0) * 1_000,
+            false => 0,
+        },
+        Ordering::Relaxed,
+    );
+    CLOCKS.usb_pll.store(
+        match usbpll_running {
+            true => config.usbpll_khz().unwrap_or(0) * 1_000,
+            false => 0,
+        },
+        Ordering::Relaxed,
+    );
+    CLOCKS.mainclk.store(12_000_000, Ordering::Relaxed);
+    CLOCKS.usb_pclk.store(0, Ordering::Relaxed);
+    CLOCKS.ssp0_pclk.store(0, Ordering::Relaxed);
+    CLOCKS.ssp1_pclk.store(0, Ordering::Relaxed);
+    CLOCKS.usart_pclk.store(0, Ordering::Relaxed);
+}
+
+/// Program SYSCON for an already validated `config`.
+unsafe fn apply(config: &ClockConfig) -> Result<(), ClockError> {
+    SYSCON.pdruncfg().modify(|w| {
+        w.set_ircout_pd(false);
+        w.set_irc_pd(false);
+    });
+    select_mainclk(MainClkSrc::Irc);
+    SYSCON.sysahbclkdiv().write(|w| w.set_div(1));
+    CLOCKS.mainclk.store(12_000_000, Ordering::Relaxed);
+
+    if let Some(khz) = config.sysosc_khz() {
+        SYSCON.sysoscctrl().write(|w| {
+            w.set_bypass(false);
+            w.set_freqrange(khz > 15_000);
+        });
+        SYSCON.pdruncfg().modify(|w| w.set_sysosc_pd(false));
+        // User manual: wait at least 500us for the oscillator to stabilize.
+        cortex_m::asm::delay(6_000);
+    }
+
+    if let Some(wdosc) = &config.wdosc {
+        SYSCON.wdtoscctrl().write(|w| {
+            w.set_freqsel(wdosc.analog_clock as u8);
+            w.set_divsel(wdosc.divsel);
+        });
+        SYSCON.pdruncfg().modify(|w| w.set_wdtosc_pd(false));
+    }
+
+    let rom_pll_mode = match (config.sys_pll_strategy, config.mainclk.source) {
+        (PllStrategy::Rom { mode, target_khz }, MainClkSrc::SysPll) => Some((mode, target_khz)),
+        _ => None,
+    };
+
+    SYSCON.pdruncfg().modify(|w| w.set_syspll_pd(true));
+    // SYSPLLCLKSEL also selects the PLL_IN main clock source, used for the system oscillator.
+    let syspll_source = match (&config.sys_pll, config.mainclk.source) {
+        (Some(pll), _) => Some(pll.source),
+        (None, MainClkSrc::SysOsc) => Some(PllClkSrc::Sysosc),
+        (None, _) => None,
+    };
+    if let Some(source) = syspll_source {
+        SYSCON
+            .syspllclksel()
+            .write(|w| w.set_sel(PllclkselSel::from_bits(source as u8)));
+        SYSCON.syspllclkuen().write(|w| w.set_ena(false));
+        SYSCON.syspllclkuen().write(|w| w.set_ena(true));
+    }
+    if let Some(pll) = &config.sys_pll
+        && rom_pll_mode.is_none()
+    {
+        SYSCON.syspllctrl().write(|w| {
+            w.set_msel(pll.m - 1);
+            w.set_psel(pll.p.trailing_zeros() as u8);
+        });
+        SYSCON.pdruncfg().modify(|w| w.set_syspll_pd(false));
+
+        if !wait_for_lock(|| SYSCON.syspllstat().read().lock()) {
+            SYSCON.pdruncfg().modify(|w| w.set_syspll_pd(true));
+            return Err(ClockError::SysPllLockTimedOut);
+        }
+    }
+
+    // The USB clock may be derived from the main clock, so stop it before anything is switched.
+    SYSCON.usbclkdiv().write(|w| w.set_div(0));
+    SYSCON.pdruncfg().modify(|w| w.set_usbpll_pd(true));
+    if let Some(pll) = &config.usb_pll {
+        SYSCON
+            .usbpllclksel()
+            .write(|w| w.set_sel(PllclkselSel::from_bits(pll.source as u8)));
+        SYSCON.usbpllclkuen().write(|w| w.set_ena(false));
+        SYSCON.usbpllclkuen().write(|w| w.set_ena(true));
+        SYSCON.usbpllctrl().write(|w| {
+            w.set_msel(pll.m - 1);
+            w.set_psel(pll.p.trailing_zeros() as u8);
+        });
+        SYSCON.pdruncfg().modify(|w| w.set_usbpll_pd(false));
+
+        if !wait_for_lock(|| SYSCON.usbpllstat().read().lock()) {
+            SYSCON.pdruncfg().modify(|w| w.set_usbpll_pd(true));
+            return Err(ClockError::UsbPllLockTimedOut);
+        }
+    }
+
+    // While parked on the 12MHz IRC any flash access time and power profile for a frequency of at
+    // least 12MHz is sufficient, so both can be programmed for the new system clock before
+    // switching to it, whether it goes up or down.
+    let mainclk_hz = match rom_pll_mode {
+        None => {
+            let mainclk_hz = config.mainclk_hz()?;
+            set_power_profile(config.power_mode, mainclk_hz.max(12_000_000))?;
+            set_flash_wait_states(flash_wait_states(mainclk_hz));
+            SYSCON
+                .sysahbclkdiv()
+                .write(|w| w.set_div(config.mainclk.divider.get()));
+            select_mainclk(config.mainclk.source);
+            mainclk_hz
+        }
+        Some((mode, target_khz)) => {
+            // The ROM picks the final frequency, so prepare for the fastest one it may settle on.
+            set_power_profile(config.power_mode, MAINCLK_MAX_KHZ * 1_000)?;
+            set_flash_wait_states(flash_wait_states(MAINCLK_MAX_KHZ * 1_000));
+
+            let Some(pll) = &config.sys_pll else {
+                return Err(ClockError::MainClkSrcDisabled);
+            };
+            let Some(input_khz) = config.pll_input_khz(pll.source) else {
+                return Err(ClockError::PllSrcDisabled);
+            };
+            let achieved_khz = unsafe {
+                power::set_pll(input_khz, target_khz, mode, PLL_LOCK_TIMEOUT)
+            }
+            .map_err(|e| match e {
+                SetPllError::NotLocked => ClockError::SysPllLockTimedOut,
+                _ => ClockError::InvalidSysPllParameters,
+            })?;
+            achieved_khz * 1_000
+        }
+    };
+    // Lower the power profile and flash access time if the new system clock allows it.
+    set_power_profile(config.power_mode, mainclk_hz)?;
+    set_flash_wait_states(flash_wait_states(mainclk_hz));
+    // The peripheral clock dividers are fed by the main clock, before the system clock divider.
+    let mainclk_src_hz = mainclk_hz * SYSCON.sysahbclkdiv().read().div() as u32;
+
+    if let Some(usb) = &config.usb_pclk {
+        SYSCON
+            .usbclksel()
+            .write(|w| w.set_sel(UsbclkselSel::from_bits(usb.source as u8)));
+        SYSCON.usbclkuen().write(|w| w.set_ena(false));
+        SYSCON.usbclkuen().write(|w| w.set_ena(true));
+        SYSCON.usbclkdiv().write(|w| w.set_div(usb.divider.get()));
+    }
+
+    SYSCON
+        .ssp0clkdiv()
+        .write(|w| w.set_div(divider_bits(config.ssp0_pclk_divider)));
+    SYSCON
+        .ssp1clkdiv()
+        .write(|w| w.set_div(divider_bits(config.ssp1_pclk_divider)));
+    SYSCON
+        .uartclkdiv()
+        .write(|w| w.set_div(divider_bits(config.usart_pclk_divider)));
+
+    // The WWDT clock selection can only be written while the WWDT register interface is clocked.
+    if let Some(wwdt_clk) = config.wwdt_clk {
+        let wwdt_clocked = SYSCON.sysahbclkctrl().read().wwdt();
+        SYSCON.sysahbclkctrl().modify(|w| w.set_wwdt(true));
+        pac::WWDT
+            .clksel()
+            .modify(|w| w.set_clksel(wwdt_clk == WwdtClkSrc::WdOsc));
+        SYSCON.sysahbclkctrl().modify(|w| w.set_wwdt(wwdt_clocked));
+    }
+
+    if config.sysosc_khz.is_none() {
+        SYSCON.pdruncfg().modify(|w| w.set_sysosc_pd(true));
+    }
+    if config.wdosc.is_none() {
+        SYSCON.pdruncfg().modify(|w| w.set_wdtosc_pd(true));
+    }
+    if config.irc == IrcConfig::Disabled {
+        SYSCON.pdruncfg().modify(|w| {
+            w.set_ircout_pd(true);
+            w.set_irc_pd(true);
+        });
+    }
+
+    let hz = |khz: Option<u32>| khz.unwrap_or(0) * 1_000;
+    let pclk_hz = |divider: Option<NonZeroU8>| match divider {
+        None => 0,
+        Some(divider) => mainclk_src_hz / divider.get() as u32,
+    };
+    let syspll_hz = match (rom_pll_mode, &config.sys_pll) {
+        (Some(_), Some(pll)) => {
+            let m = SYSCON.syspllctrl().read().msel() as u32 + 1;
+            hz(config.pll_input_khz(pll.source)) * m
+        }
+        _ => hz(config.syspll_khz()),
+    };
+    let usb_pclk_hz = match &config.usb_pclk {
+        Some(UsbClkConfig {
+            source: UsbClkSrc::MainClk,
+            divider,
+        }) => pclk_hz(Some(*divider)),
+        _ => hz(config.usbclk_khz()),
+    };
+
+    CLOCKS.irc.store(hz(config.irc_khz()), Ordering::Relaxed);
+    CLOCKS.sysosc.store(hz(config.sysosc_khz()), Ordering::Relaxed);
+    CLOCKS
+        .wdosc
+        .store(config.wdosc_hz().unwrap_or(0), Ordering::Relaxed);
+    CLOCKS.sys_pll.store(syspll_hz, Ordering::Relaxed);
+    CLOCKS.usb_pll.store(hz(config.usbpll_khz()), Ordering::Relaxed);
+    CLOCKS.mainclk.store(mainclk_hz, Ordering::Relaxed);
+    CLOCKS.usb_pclk.store(usb_pclk_hz, Ordering::Relaxed);
+    CLOCKS
+        .ssp0_pclk
+        .store(pclk_hz(config.ssp0_pclk_divider), Ordering::Relaxed);
+    CLOCKS
+        .ssp1_pclk
+        .store(pclk_hz(config.ssp1_pclk_divider), Ordering::Relaxed);
+    CLOCKS
+        .usart_pclk
+        .store(pclk_hz(config.usart_pclk_divider), Ordering::Relaxed);
+    CLOCKS
+        .wwdt_clk
+        .store(config.wwdt_clk_hz().unwrap_or(0), Ordering::Relaxed);
+
+    Ok(())
+}
+
+#[inline]
+fn select_mainclk(source: MainClkSrc) {
+    SYSCON
+        .mainclksel()
+        .write(|w| w.set_sel(MainclkselSel::from_bits(source as u8)));
+    SYSCON.mainclkuen().write(|w| w.set_ena(false));
+    SYSCON.mainclkuen().write(|w| w.set_ena(true));
+}
+
+/// Switch the clock tree to `config` at runtime.
+///
+/// The configuration is validated before anything is touched. The switch happens with interrupts
+/// disabled and follows the same sequence as [`init`]. Afterwards the [`epoch`] is advanced so
+/// that code polling it recomputes its dividers; peripherals that are mid-transfer while the
+/// switch happens may see a corrupted transfer.
+///
+/// If the switch fails, the main clock is left running from the IRC as described for [`init`].
+/// The epoch is still advanced, since the reported frequencies changed.
+pub fn reconfigure(config: ClockConfig) -> Result<(), ClockError> {
+    config.validate()?;
+
+    critical_section::with(|_| {
+        let result = unsafe { switch(&config) };
+        // No atomic read-modify-write on thumbv6m, but interrupts are masked here.
+        EPOCH.store(EPOCH.load(Ordering::Relaxed).wrapping_add(1), Ordering::Release);
+        result
+    })
+}
+
+#[inline]
+fn set_power_profile(mode: PowerMode, cclk_hz: u32) -> Result<(), ClockError> {
+    power::set_power(mode, cclk_hz.div_ceil(1_000_000)).map_err(|_| ClockError::PowerProfileRejected)
+}
+
+/// Flash access time in system clocks, minus one (FLASHTIM), needed at `mainclk_hz`.
+///
+/// User manual: 1 clock up to 20MHz, 2 clocks up to 40MHz, 3 clocks up to 50MHz.
+#[inline]
+const fn flash_wait_states(mainclk_hz: u32) -> u8 {
+    match mainclk_hz {
+        0..=20_000_000 => 0,
+        20_000_001..=40_000_000 => 1,
+        _ => 2,
+    }
+}
+
+#[inline]
+fn set_flash_wait_states(flashtim: u8) {
+    // The reserved bits of FLASHCFG must not be changed.
+    pac::FLASHCTRL
+        .flashcfg()
+        .modify(|w| w.set_flashtim(flashtim));
+}
+
+#[inline]
+fn wait_for_lock(locked: impl Fn() -> bool) -> bool {
+    (0..PLL_LOCK_TIMEOUT).any(|_| locked())
+}
+
+/// Peripheral clock dividers use 0 to gate the clock.
+#[inline]
+const fn divider_bits(divider: Option<NonZeroU8>) -> u8 {
+    match divider {
+        None => 0,
+        Some(divider) => divider.get(),
+    }
+}
+
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ClkOutSrc {
+    Irc = 0,
+    SysOsc = 1,
+    WdOsc = 2,
+    /// The main clock, before the system clock divider.
+    MainClk = 3,
+}
+
+/// Drives the CLKOUT function of PIO0_1 with a divided internal clock.
+pub struct ClkOut<'d> {
+    pin: Peri<'d, peripherals::PIO0_1>,
+}
+
+impl<'d> ClkOut<'d> {
+    /// Output `source` divided by `divider` on PIO0_1.
+    pub fn new(
+        pin: Peri<'d, peripherals::PIO0_1>,
+        source: ClkOutSrc,
+        divider: NonZeroU8,
+    ) -> Result<Self, ClockError> {
+        let mut clkout = Self { pin };
+        clkout.set_source(source, divider)?;
+        clkout.pin.iocon().modify(|r| r.set_func(1));
+        Ok(clkout)
+    }
+
+    /// Change the source and divider. The output keeps its previous setting on error.
+    pub fn set_source(&mut self, source: ClkOutSrc, divider: NonZeroU8) -> Result<(), ClockError> {
+        let src_hz = clkout_src_hz(source);
+        if src_hz == 0 {
+            return Err(ClockError::ClkOutSrcDisabled);
+        }
+
+        SYSCON.clkoutsel().write(|w| w.set_sel(source as u8));
+        SYSCON.clkoutuen().write(|w| w.set_ena(false));
+        SYSCON.clkoutuen().write(|w| w.set_ena(true));
+        SYSCON.clkoutdiv().write(|w| w.set_div(divider.get()));
+
+        CLOCKS
+            .clkout
+            .store(src_hz / divider.get() as u32, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Frequency currently output on the pin.
+    #[inline]
+    pub fn frequency(&self) -> u32 {
+        clkout_hz()
+    }
+}
+
+impl<'d> Drop for ClkOut<'d> {
+    fn drop(&mut self) {
+        SYSCON.clkoutdiv().write(|w| w.set_div(0));
+        self.pin.iocon().modify(|r| r.set_func(self.pin.pio_func()));
+        CLOCKS.clkout.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Frequency of a CLKOUT source, or 0 if it is not running.
+fn clkout_src_hz(source: ClkOutSrc) -> u32 {
+    match source {
+        ClkOutSrc::Irc => irc_hz(),
+        ClkOutSrc::SysOsc => sysosc_hz(),
+        ClkOutSrc::WdOsc => wdosc_hz(),
+        ClkOutSrc::MainClk => mainclk_hz() * SYSCON.sysahbclkdiv().read().div() as u32,
+    }
+}
+
+/// Recompute the CLKOUT frequency from CLKOUTSEL and CLKOUTDIV after the clock tree changed.
+fn update_clkout_hz() {
+    let source = match SYSCON.clkoutsel().read().sel() {
+        0 => ClkOutSrc::Irc,
+        1 => ClkOutSrc::SysOsc,
+        2 => ClkOutSrc::WdOsc,
+        _ => ClkOutSrc::MainClk,
+    };
+    let hz = match SYSCON.clkoutdiv().read().div() {
+        0 => 0,
+        div => clkout_src_hz(source) / div as u32,
+    };
+    CLOCKS.clkout.store(hz, Ordering::Relaxed);
+}
+
+pub(crate) trait SealedSysconPeripheral {
+    fn set_clock(enabled: bool);
+    fn set_reset(asserted: bool);
+}
+
+/// A peripheral with an AHB clock gate in SYSAHBCLKCTRL and, for SSP0, SSP1 and I2C, a reset in
+/// PRESETCTRL.
+#[allow(private_bounds)]
+pub trait SysconPeripheral: SealedSysconPeripheral + PeripheralType + 'static {
+    /// Enable the peripheral's AHB clock, giving access to its registers.
+    #[inline]
+    fn enable_clock() {
+        Self::set_clock(true);
+    }
+
+    /// Gate the peripheral's AHB clock.
+    #[inline]
+    fn disable_clock() {
+        Self::set_clock(false);
+    }
+
+    /// Pulse the peripheral's reset, if it has one.
+    #[inline]
+    fn reset() {
+        Self::set_reset(true);
+        Self::set_reset(false);
+    }
+}
+
+macro_rules! impl_syscon_peripheral {
+    ($peri:ident, $clk:ident) => {
+        impl SealedSysconPeripheral for peripherals::$peri {
+            #[inline]
+            fn set_clock(enabled: bool) {
+                critical_section::with(|_| {
+                    SYSCON.sysahbclkctrl().modify(|w| w.$clk(enabled));
+                });
+            }
+
+            #[inline]
+            fn set_reset(_asserted: bool) {}
+        }
+
+        impl SysconPeripheral for peripherals::$peri {}
+    };
+    ($peri:ident, $clk:ident, $rst:ident) => {
+        impl SealedSysconPeripheral for peripherals::$peri {
+            #[inline]
+            fn set_clock(enabled: bool) {
+                critical_section::with(|_| {
+                    SYSCON.sysahbclkctrl().modify(|w| w.$clk(enabled));
+                });
+            }
+
+            #[inline]
+            fn set_reset(asserted: bool) {
+                critical_section::with(|_| {
+                    SYSCON.presetctrl().modify(|w| w.$rst(!asserted));
+                });
+            }
+        }
+
+        impl SysconPeripheral for peripherals::$peri {}
+    };
+}
+
+impl_syscon_peripheral!(I2C, set_i2c, set_i2c_rst_n);
+impl_syscon_peripheral!(CT16B0, set_ct16b0);
+impl_syscon_peripheral!(CT16B1, set_ct16b1);
+impl_syscon_peripheral!(CT32B0, set_ct32b0);
+impl_syscon_peripheral!(CT32B1, set_ct32b1);
+impl_syscon_peripheral!(SSP0, set_ssp0, set_ssp0_rst_n);
+impl_syscon_peripheral!(USART, set_usart);
+impl_syscon_peripheral!(ADC, set_adc);
+impl_syscon_peripheral!(USB, set_usb);
+impl_syscon_peripheral!(WWDT, set_wwdt);
+impl_syscon_peripheral!(SSP1, set_ssp1, set_ssp1_rst_n);
+
+/// Keeps a peripheral clocked for as long as it is held.
+///
+/// Drivers create one in `new()`, which resets the peripheral and enables its clock, and the
+/// clock is gated again when the driver is dropped.
+pub(crate) struct PeripheralClock<'d, T: SysconPeripheral> {
+    _peri: PhantomData<&'d mut T>,
+}
+
+impl<'d, T: SysconPeripheral> PeripheralClock<'d, T> {
+    #[inline]
+    pub(crate) fn new(_peri: &Peri<'d, T>) -> Self {
+        T::enable_clock();
+        T::reset();
+        Self { _peri: PhantomData }
+    }
+}
+
+impl<'d, T: SysconPeripheral> Drop for PeripheralClock<'d, T> {
+    #[inline]
+    fn drop(&mut self) {
+        T::disable_clock();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn irc_presets_reach_their_target() {
+        let config = ClockConfig::irc_24mhz();
+        assert_eq!(config.mainclk.source, MainClkSrc::SysPll);
+        assert_eq!(config.mainclk_khz(), Ok(24_000));
+
+        let config = ClockConfig::irc_48mhz();
+        assert_eq!(config.mainclk.source, MainClkSrc::SysPll);
+        assert_eq!(config.mainclk_khz(), Ok(48_000));
+    }
+
+    #[test]
+    fn for_target_divides_the_pll_output() {
+        // 30MHz needs a 60MHz PLL output and a system clock divider of 2.
+        let config = ClockConfig::for_target(PllClkSrc::Sysosc, 12_000, 30_000, false).unwrap();
+        assert_eq!(config.mainclk_khz(), Ok(30_000));
+        assert_eq!(config.syspll_khz(), Some(60_000));
+        assert_eq!(config.mainclk.divider.get(), 2);
+    }
+
+    #[test]
+    fn for_target_shares_the_main_clock_with_usb() {
+        let config = ClockConfig::for_target(PllClkSrc::Sysosc, 12_000, 48_000, true).unwrap();
+        assert_eq!(config.mainclk_khz(), Ok(48_000));
+        assert_eq!(config.usbclk_khz(), Some(48_000));
+        assert_eq!(config.usb_pll, None);
+    }
+
+    #[test]
+    fn for_target_falls_back_to_the_usb_pll() {
+        let config = ClockConfig::for_target(PllClkSrc::Sysosc, 12_000, 30_000, true).unwrap();
+        assert_eq!(config.mainclk_khz(), Ok(30_000));
+        assert_eq!(config.usbclk_khz(), Some(48_000));
+        assert!(config.usb_pll.is_some());
+    }
+
+    #[test]
+    fn for_target_rejects_bad_inputs() {
+        assert_eq!(
+            ClockConfig::for_target(PllClkSrc::Irc, 16_000, 24_000, false).err(),
+            Some(ClockError::IrcInputMismatch)
+        );
+        assert_eq!(
+            ClockConfig::for_target(PllClkSrc::Irc, 12_000, 60_000, false).err(),
+            Some(ClockError::SysClkOutOfRange)
+        );
+        assert_eq!(
+            ClockConfig::for_target(PllClkSrc::Irc, 12_000, 48_000, true).err(),
+            Some(ClockError::UsbClkSrcDisabled)
+        );
+    }
+}
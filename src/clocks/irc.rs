@@ -0,0 +1,339 @@
+//! IRC accuracy measurement and trimming.
+//!
+//! The 12MHz IRC is factory trimmed to +/- 1%. Boards without a crystal on the main clock can
+//! measure it against a more accurate reference and correct it through IRCCTRL:
+//! - [`Reference::SysOsc`]: the system oscillator. The main clock must be derived from the system
+//!   oscillator, and the windowed watchdog (clocked from the IRC) is used as the counter. This
+//!   starts the watchdog permanently, so it takes the watchdog for good.
+//! - [`Reference::Capture`]: a signal of known frequency on CT32B0_CAP0 (PIO0_17), e.g. a GPS
+//!   1PPS output. The main clock must be derived from the IRC.
+//! - [`Reference::UsbSof`]: the 1kHz USB start-of-frame of a configured USB device. The main clock
+//!   must be derived from the IRC.
+//!
+//! CT32B0 is used as the timebase for all references. The trim value can be persisted with
+//! [`save_trim`] and restored at boot with [`restore_trim`].
+
+use embassy_hal_internal::Peri;
+use embedded_storage::{ReadStorage, Storage};
+
+use crate::{
+    gpio::SealedPin,
+    pac::{self, SYSCON},
+    peripherals::{CT32B0, PIO0_17, WWDT},
+};
+
+use super::{PeripheralClock, irc_hz, mainclk_hz, sysosc_hz};
+
+/// Nominal IRC frequency.
+const IRC_HZ: u32 = 12_000_000;
+
+/// Length of a measurement when the reference does not impose one.
+const WINDOW_MS: u32 = 100;
+
+/// The windowed watchdog counts its clock divided by 4.
+const WWDT_PRESCALER: u32 = 4;
+
+/// IOCON function of PIO0_17 for CT32B0_CAP0.
+const CT32B0_CAP0_FUNC: u8 = 2;
+
+/// Reference clock the IRC is measured against.
+pub enum Reference<'a> {
+    /// The system oscillator, through the main clock, counted against the windowed watchdog.
+    ///
+    /// The first measurement starts the watchdog, which can not be stopped again before the next
+    /// reset. It keeps counting without resetting the chip, so it can no longer serve as a
+    /// watchdog and is consumed here for good.
+    SysOsc { wwdt: Peri<'static, WWDT> },
+    /// Rising edges of a `hz` signal on CT32B0_CAP0. The signal should not be faster than a few
+    /// kHz, since every edge is polled.
+    Capture { pin: Peri<'a, PIO0_17>, hz: u32 },
+    /// USB start-of-frame, every 1ms.
+    UsbSof,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum IrcError {
+    /// The main clock is not derived from the clock required by the reference.
+    WrongMainClkSrc,
+    /// The IRC or the reference clock is not running.
+    ClkDisabled,
+    /// No reference edge arrived within twice the expected time.
+    Timeout,
+    /// The IRC error could not be brought within the requested tolerance.
+    OutOfTrimRange,
+    /// The windowed watchdog was already started with chip reset enabled.
+    WatchdogRunning,
+}
+
+/// Result of a measurement.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct IrcMeasurement {
+    /// Deviation of the IRC from 12MHz, in parts per million. Positive when the IRC is fast.
+    pub error_ppm: i32,
+    /// IRCCTRL trim value in effect during the measurement.
+    pub trim: u8,
+}
+
+/// Current IRCCTRL trim value.
+#[inline]
+pub fn trim() -> u8 {
+    SYSCON.ircctrl().read().trim()
+}
+
+/// Set the IRCCTRL trim value. Higher values make the IRC faster.
+#[inline]
+pub fn set_trim(trim: u8) {
+    SYSCON.ircctrl().write(|w| w.set_trim(trim));
+}
+
+/// Measure the IRC against `reference`.
+pub fn measure(
+    timer: &mut Peri<'_, CT32B0>,
+    reference: &mut Reference<'_>,
+) -> Result<IrcMeasurement, IrcError> {
+    if irc_hz() == 0 {
+        return Err(IrcError::ClkDisabled);
+    }
+
+    let _clock = PeripheralClock::new(timer);
+    let (measured, expected) = match reference {
+        Reference::SysOsc { wwdt } => measure_sysosc(wwdt)?,
+        Reference::Capture { pin, hz } => {
+            let hz = *hz;
+            if hz == 0 {
+                return Err(IrcError::ClkDisabled);
+            }
+            pin.iocon().modify(|r| r.set_func(CT32B0_CAP0_FUNC));
+            let periods = (hz * WINDOW_MS / 1_000).max(1);
+            let result = measure_edges(periods, hz, capture_edge);
+            pin.iocon().modify(|r| r.set_func(pin.pio_func()));
+            result?
+        }
+        Reference::UsbSof => measure_edges(WINDOW_MS, 1_000, sof_edge)?,
+    };
+
+    let error_ppm = (measured as i64 - expected as i64) * 1_000_000 / expected as i64;
+    Ok(IrcMeasurement {
+        error_ppm: error_ppm as i32,
+        trim: trim(),
+    })
+}
+
+/// Adjust the IRC trim until its error against `reference` is within `tolerance_ppm`, or no
+/// better trim value exists.
+///
+/// The best trim value found is left in effect and returned with its measurement.
+pub fn calibrate(
+    timer: &mut Peri<'_, CT32B0>,
+    reference: &mut Reference<'_>,
+    tolerance_ppm: u32,
+) -> Result<IrcMeasurement, IrcError> {
+    let mut best = measure(timer, reference)?;
+
+    loop {
+        if best.error_ppm.unsigned_abs() <= tolerance_ppm {
+            return Ok(best);
+        }
+
+        // A fast IRC needs a lower trim value.
+        let next_trim = match best.error_ppm > 0 {
+            true => best.trim.checked_sub(1),
+            false => best.trim.checked_add(1),
+        };
+        let Some(next_trim) = next_trim else {
+            return Err(IrcError::OutOfTrimRange);
+        };
+
+        set_trim(next_trim);
+        let measurement = measure(timer, reference)?;
+        if measurement.error_ppm.unsigned_abs() >= best.error_ppm.unsigned_abs() {
+            // Overshot: the previous value was the closest one.
+            set_trim(best.trim);
+            return match best.error_ppm.unsigned_abs() <= tolerance_ppm {
+                true => Ok(best),
+                false => Err(IrcError::OutOfTrimRange),
+            };
+        }
+        best = measurement;
+    }
+}
+
+/// Marker preceding a persisted trim value.
+const TRIM_MAGIC: u8 = 0x1C;
+
+/// Persist `trim` at `offset` in `storage`, using 3 bytes.
+pub fn save_trim<S: Storage>(storage: &mut S, offset: u32, trim: u8) -> Result<(), S::Error> {
+    storage.write(offset, &[TRIM_MAGIC, trim, !trim])
+}
+
+/// Read a trim value persisted with [`save_trim`], or `None` if none was saved.
+pub fn load_trim<S: ReadStorage>(storage: &mut S, offset: u32) -> Result<Option<u8>, S::Error> {
+    let mut buf = [0u8; 3];
+    storage.read(offset, &mut buf)?;
+
+    Ok(match buf {
+        [TRIM_MAGIC, trim, check] if check == !trim => Some(trim),
+        _ => None,
+    })
+}
+
+/// Apply a trim value persisted with [`save_trim`]. Returns whether one was found.
+pub fn restore_trim<S: ReadStorage>(storage: &mut S, offset: u32) -> Result<bool, S::Error> {
+    let trim = load_trim(storage, offset)?;
+    if let Some(trim) = trim {
+        set_trim(trim);
+    }
+    Ok(trim.is_some())
+}
+
+/// Source of the PLL input and main clock, as currently programmed.
+#[inline]
+fn mainclk_from(source: pac::syscon::vals::PllclkselSel) -> bool {
+    use pac::syscon::vals::{MainclkselSel, PllclkselSel};
+
+    match SYSCON.mainclksel().read().sel() {
+        MainclkselSel::IRC => source == PllclkselSel::IRC,
+        MainclkselSel::PLL_IN | MainclkselSel::PLL_OUT => {
+            SYSCON.syspllclksel().read().sel() == source
+        }
+        _ => false,
+    }
+}
+
+/// Count IRC/4 ticks on the windowed watchdog over a window timed by the sysosc-derived system
+/// clock. Returns the measured and expected tick counts.
+///
+/// The watchdog clock selection and timeout are restored afterwards, but the watchdog keeps
+/// counting, without resetting the chip, until the next reset.
+fn measure_sysosc(_wwdt: &mut Peri<'static, WWDT>) -> Result<(u32, u32), IrcError> {
+    if sysosc_hz() == 0 {
+        return Err(IrcError::ClkDisabled);
+    }
+    if !mainclk_from(pac::syscon::vals::PllclkselSel::SYSOSC) {
+        return Err(IrcError::WrongMainClkSrc);
+    }
+
+    let wwdt_clocked = SYSCON.sysahbclkctrl().read().wwdt();
+    SYSCON.sysahbclkctrl().modify(|w| w.set_wwdt(true));
+
+    if pac::WWDT.mod_().read().wdreset() {
+        SYSCON.sysahbclkctrl().modify(|w| w.set_wwdt(wwdt_clocked));
+        return Err(IrcError::WatchdogRunning);
+    }
+
+    let clksel = pac::WWDT.clksel().read().clksel();
+    let timeout = pac::WWDT.tc().read().count();
+    pac::WWDT.clksel().modify(|w| w.set_clksel(false));
+    pac::WWDT.tc().write(|w| w.set_count(0x00FF_FFFF));
+    pac::WWDT.mod_().modify(|w| w.set_wden(true));
+    feed_wwdt();
+
+    let window_ticks = mainclk_hz() / 1_000 * WINDOW_MS;
+    let result = with_timer(|| {
+        let start_tc = timer_count();
+        let start = pac::WWDT.tv().read().count();
+        while timer_count().wrapping_sub(start_tc) < window_ticks {}
+        let end = pac::WWDT.tv().read().count();
+        Ok(start.wrapping_sub(end) & 0x00FF_FFFF)
+    });
+
+    pac::WWDT.clksel().modify(|w| w.set_clksel(clksel));
+    pac::WWDT.tc().write(|w| w.set_count(timeout));
+    feed_wwdt();
+    SYSCON.sysahbclkctrl().modify(|w| w.set_wwdt(wwdt_clocked));
+
+    let expected = IRC_HZ / WWDT_PRESCALER / 1_000 * WINDOW_MS;
+    Ok((result?, expected))
+}
+
+#[inline]
+fn feed_wwdt() {
+    critical_section::with(|_| {
+        pac::WWDT.feed().write(|w| w.set_feed(0xAA));
+        pac::WWDT.feed().write(|w| w.set_feed(0x55));
+    });
+}
+
+/// Count IRC-derived system clock ticks over `periods` periods of a `hz` reference signalled by
+/// `edge`. Returns the measured and expected tick counts.
+fn measure_edges(
+    periods: u32,
+    hz: u32,
+    edge: fn() -> Option<u32>,
+) -> Result<(u32, u32), IrcError> {
+    if !mainclk_from(pac::syscon::vals::PllclkselSel::IRC) {
+        return Err(IrcError::WrongMainClkSrc);
+    }
+
+    let expected = (mainclk_hz() as u64 * periods as u64 / hz as u64) as u32;
+    let period_timeout = 2 * (mainclk_hz() / hz);
+
+    let measured = with_timer(|| {
+        let wait_edge = || {
+            let since = timer_count();
+            loop {
+                if let Some(t) = edge() {
+                    return Ok(t);
+                }
+                if timer_count().wrapping_sub(since) > period_timeout {
+                    return Err(IrcError::Timeout);
+                }
+            }
+        };
+
+        // Discard a stale event, then synchronize to a fresh edge.
+        let _ = edge();
+        let start = wait_edge()?;
+        let mut end = start;
+        for _ in 0..periods {
+            end = wait_edge()?;
+        }
+        Ok(end.wrapping_sub(start))
+    })?;
+
+    Ok((measured, expected))
+}
+
+/// Timer value captured on the last rising edge of CT32B0_CAP0, if one occurred.
+fn capture_edge() -> Option<u32> {
+    let ct = pac::CT32B0;
+    if !ct.ir().read().cr0int() {
+        return None;
+    }
+    ct.ir().write(|w| w.set_cr0int(true));
+    Some(ct.cr(0).read().cap())
+}
+
+/// Timer value at the last USB start-of-frame, if one occurred.
+fn sof_edge() -> Option<u32> {
+    if !pac::USB.intstat().read().frame_int() {
+        return None;
+    }
+    let t = timer_count();
+    pac::USB.intstat().write(|w| w.set_frame_int(true));
+    Some(t)
+}
+
+#[inline]
+fn timer_count() -> u32 {
+    pac::CT32B0.tc().read().tcval()
+}
+
+/// Run `f` with CT32B0, which must already be clocked, free-running from the system clock and
+/// capturing rising edges on CAP0.
+fn with_timer<T>(f: impl FnOnce() -> Result<T, IrcError>) -> Result<T, IrcError> {
+    let ct = pac::CT32B0;
+    ct.tcr().write(|w| w.set_crst(true));
+    ct.pr().write(|w| w.set_pcval(0));
+    ct.ccr().write(|w| w.set_cap0re(true));
+    ct.ir().write(|w| w.set_cr0int(true));
+    ct.tcr().write(|w| w.set_cen(true));
+
+    let result = f();
+
+    ct.tcr().write(|w| w.set_cen(false));
+    ct.ccr().write(|w| w.set_cap0re(false));
+    result
+}
@@ -0,0 +1,556 @@
+//! Structured data on top of the [`EEPROM`][crate::peripherals::EEPROM] storage.
+//!
+//! Everything here is generic over [`embedded_storage::Storage`], so it can be exercised on the
+//! host against an in-memory storage.
+
+use core::marker::PhantomData;
+
+use embedded_storage::{ReadStorage, Storage};
+
+/// CRC-16/CCITT-FALSE over `bytes`, continuing from `crc` (0xFFFF for a new checksum).
+pub const fn crc16(mut crc: u16, bytes: &[u8]) -> u16 {
+    let mut i = 0;
+    while i < bytes.len() {
+        crc ^= (bytes[i] as u16) << 8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = match crc & 0x8000 != 0 {
+                true => (crc << 1) ^ 0x1021,
+                false => crc << 1,
+            };
+            bit += 1;
+        }
+        i += 1;
+    }
+    crc
+}
+
+/// `a` was written after `b`, allowing the sequence number to wrap.
+#[inline]
+const fn is_newer(a: u16, b: u16) -> bool {
+    (a.wrapping_sub(b) as i16) > 0
+}
+
+/// A value that can be kept in a [`Record`], serialized to `N` bytes.
+pub trait RecordData<const N: usize>: Default {
+    /// Layout version. Copies stored with another version are ignored.
+    const VERSION: u16;
+
+    fn to_bytes(&self) -> [u8; N];
+
+    /// Deserialize, or `None` if `bytes` do not hold a valid value.
+    fn from_bytes(bytes: &[u8; N]) -> Option<Self>;
+}
+
+/// Version, sequence number and CRC preceding each copy.
+const RECORD_HEADER_SIZE: usize = 6;
+
+/// `N` bytes framed by an `H`-byte header and a `T`-byte trailer, laid out as stored so a slot can
+/// be programmed with a single write.
+#[repr(C)]
+struct Frame<const H: usize, const N: usize, const T: usize> {
+    header: [u8; H],
+    data: [u8; N],
+    trailer: [u8; T],
+}
+
+impl<const H: usize, const N: usize, const T: usize> Frame<H, N, T> {
+    #[inline]
+    fn as_bytes(&self) -> &[u8] {
+        // SAFETY: a `repr(C)` struct of byte arrays has no padding.
+        unsafe { core::slice::from_raw_parts((self as *const Self).cast::<u8>(), H + N + T) }
+    }
+}
+
+/// A `T` stored as two alternating, CRC-protected copies.
+///
+/// Each [`store`][Self::store] overwrites the older copy, so a power loss during a write leaves
+/// the previous value intact. A slot is laid out as version, sequence number and CRC-16 (each
+/// little-endian `u16`), followed by the `N` data bytes.
+pub struct Record<T: RecordData<N>, const N: usize> {
+    offset: u32,
+    /// Sequence number and slot of the newest valid copy.
+    current: Option<(u16, usize)>,
+    _data: PhantomData<T>,
+}
+
+impl<T: RecordData<N>, const N: usize> Record<T, N> {
+    /// Bytes of storage used by a record, starting at its offset.
+    pub const SIZE: usize = 2 * (RECORD_HEADER_SIZE + N);
+
+    /// A record occupying [`SIZE`][Self::SIZE] bytes at `offset`.
+    pub const fn new(offset: u32) -> Self {
+        Self {
+            offset,
+            current: None,
+            _data: PhantomData,
+        }
+    }
+
+    #[inline]
+    const fn slot_offset(&self, slot: usize) -> u32 {
+        self.offset + (slot * (RECORD_HEADER_SIZE + N)) as u32
+    }
+
+    /// Read one slot, returning its sequence number and value if it is valid.
+    fn read_slot<S: ReadStorage>(
+        &self,
+        storage: &mut S,
+        slot: usize,
+    ) -> Result<Option<(u16, T)>, S::Error> {
+        let mut header = [0u8; RECORD_HEADER_SIZE];
+        let mut data = [0u8; N];
+        storage.read(self.slot_offset(slot), &mut header)?;
+        storage.read(
+            self.slot_offset(slot) + RECORD_HEADER_SIZE as u32,
+            &mut data,
+        )?;
+
+        let version = u16::from_le_bytes([header[0], header[1]]);
+        let seq = u16::from_le_bytes([header[2], header[3]]);
+        let crc = u16::from_le_bytes([header[4], header[5]]);
+
+        if version != T::VERSION || crc != crc16(crc16(0xFFFF, &header[..4]), &data) {
+            return Ok(None);
+        }
+        Ok(T::from_bytes(&data).map(|value| (seq, value)))
+    }
+
+    /// Read the newest valid copy, or `T::default()` if neither copy is valid.
+    pub fn load<S: ReadStorage>(&mut self, storage: &mut S) -> Result<T, S::Error> {
+        let a = self.read_slot(storage, 0)?;
+        let b = self.read_slot(storage, 1)?;
+
+        let (current, value) = match (a, b) {
+            (Some((seq_a, a)), Some((seq_b, b))) => match is_newer(seq_b, seq_a) {
+                true => (Some((seq_b, 1)), b),
+                false => (Some((seq_a, 0)), a),
+            },
+            (Some((seq, a)), None) => (Some((seq, 0)), a),
+            (None, Some((seq, b))) => (Some((seq, 1)), b),
+            (None, None) => (None, T::default()),
+        };
+
+        self.current = current;
+        Ok(value)
+    }
+
+    /// Write `value` over the older copy.
+    ///
+    /// Call [`load`][Self::load] first, or the copy to overwrite is not known and the first slot
+    /// is used.
+    pub fn store<S: Storage>(&mut self, storage: &mut S, value: &T) -> Result<(), S::Error> {
+        let (seq, slot) = match self.current {
+            Some((seq, slot)) => (seq.wrapping_add(1), 1 - slot),
+            None => (0, 0),
+        };
+
+        let mut frame = Frame::<RECORD_HEADER_SIZE, N, 0> {
+            header: [0; RECORD_HEADER_SIZE],
+            data: value.to_bytes(),
+            trailer: [],
+        };
+        frame.header[0..2].copy_from_slice(&T::VERSION.to_le_bytes());
+        frame.header[2..4].copy_from_slice(&seq.to_le_bytes());
+        let crc = crc16(crc16(0xFFFF, &frame.header[..4]), &frame.data);
+        frame.header[4..6].copy_from_slice(&crc.to_le_bytes());
+
+        // One write, so the slot costs a single program cycle; the CRC catches a torn write.
+        storage.write(self.slot_offset(slot), frame.as_bytes())?;
+
+        self.current = Some((seq, slot));
+        Ok(())
+    }
+}
+
+/// Sequence number and CRC surrounding each log entry.
+const LOG_OVERHEAD: usize = 6;
+
+/// An append-only log of `N`-byte entries, written round-robin over a window of storage so every
+/// cell wears at the same rate.
+///
+/// Each slot holds a little-endian `u32` sequence number, the entry, and a CRC-16 over both. After
+/// a reset, [`recover`][Self::recover] finds the newest entry and the run of consecutive entries
+/// preceding it. When the window is full, the oldest entry is overwritten.
+pub struct RingLog<const N: usize> {
+    start: u32,
+    slots: usize,
+    /// Slot of the newest entry.
+    head: usize,
+    /// Number of entries ending at `head`.
+    len: usize,
+    next_seq: u32,
+}
+
+impl<const N: usize> RingLog<N> {
+    /// Bytes of storage used by each entry.
+    pub const SLOT_SIZE: usize = N + LOG_OVERHEAD;
+
+    /// A log over `size` bytes of storage at `start`. Call [`recover`][Self::recover] before
+    /// using a log written before the last reset.
+    ///
+    /// # Panics
+    /// Panics if the window does not fit at least two entries.
+    pub const fn new(start: u32, size: usize) -> Self {
+        let slots = size / Self::SLOT_SIZE;
+        if slots < 2 {
+            ::core::panic!("RingLog window must fit at least two entries");
+        }
+
+        Self {
+            start,
+            slots,
+            head: slots - 1,
+            len: 0,
+            next_seq: 0,
+        }
+    }
+
+    /// Number of entries the window can hold.
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        self.slots
+    }
+
+    /// Number of entries in the log.
+    #[inline]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Sequence number the next entry will get.
+    #[inline]
+    pub const fn next_seq(&self) -> u32 {
+        self.next_seq
+    }
+
+    #[inline]
+    const fn slot_offset(&self, slot: usize) -> u32 {
+        self.start + (slot * Self::SLOT_SIZE) as u32
+    }
+
+    /// Read one slot, returning its sequence number and entry if it is valid.
+    fn read_slot<S: ReadStorage>(
+        &self,
+        storage: &mut S,
+        slot: usize,
+    ) -> Result<Option<(u32, [u8; N])>, S::Error> {
+        let mut seq = [0u8; 4];
+        let mut data = [0u8; N];
+        let mut crc = [0u8; 2];
+        let offset = self.slot_offset(slot);
+        storage.read(offset, &mut seq)?;
+        storage.read(offset + 4, &mut data)?;
+        storage.read(offset + 4 + N as u32, &mut crc)?;
+
+        match u16::from_le_bytes(crc) == crc16(crc16(0xFFFF, &seq), &data) {
+            true => Ok(Some((u32::from_le_bytes(seq), data))),
+            false => Ok(None),
+        }
+    }
+
+    /// Find the newest entry and the consecutive entries before it.
+    pub fn recover<S: ReadStorage>(&mut self, storage: &mut S) -> Result<(), S::Error> {
+        let mut newest: Option<(u32, usize)> = None;
+        for slot in 0..self.slots {
+            if let Some((seq, _)) = self.read_slot(storage, slot)? {
+                let is_newest = match newest {
+                    Some((newest_seq, _)) => (seq.wrapping_sub(newest_seq) as i32) > 0,
+                    None => true,
+                };
+                if is_newest {
+                    newest = Some((seq, slot));
+                }
+            }
+        }
+
+        let Some((head_seq, head)) = newest else {
+            self.head = self.slots - 1;
+            self.len = 0;
+            self.next_seq = 0;
+            return Ok(());
+        };
+
+        // Walk back while the sequence numbers are consecutive; a torn or stale slot ends the log.
+        let mut len = 1;
+        while len < self.slots {
+            let slot = (head + self.slots - len) % self.slots;
+            match self.read_slot(storage, slot)? {
+                Some((seq, _)) if seq == head_seq.wrapping_sub(len as u32) => len += 1,
+                _ => break,
+            }
+        }
+
+        self.head = head;
+        self.len = len;
+        self.next_seq = head_seq.wrapping_add(1);
+        Ok(())
+    }
+
+    /// Append `entry`, overwriting the oldest entry if the log is full. Returns its sequence
+    /// number.
+    pub fn push<S: Storage>(&mut self, storage: &mut S, entry: &[u8; N]) -> Result<u32, S::Error> {
+        let slot = (self.head + 1) % self.slots;
+        let seq = self.next_seq.to_le_bytes();
+        let frame = Frame::<4, N, 2> {
+            header: seq,
+            data: *entry,
+            trailer: crc16(crc16(0xFFFF, &seq), entry).to_le_bytes(),
+        };
+
+        // Written in one go to spare program cycles; a torn slot fails its CRC in `recover`.
+        storage.write(self.slot_offset(slot), frame.as_bytes())?;
+
+        let pushed = self.next_seq;
+        self.head = slot;
+        self.len = (self.len + 1).min(self.slots);
+        self.next_seq = self.next_seq.wrapping_add(1);
+        Ok(pushed)
+    }
+
+    /// Read the `index`th entry, counting from the oldest, with its sequence number.
+    ///
+    /// Returns `None` if `index` is out of range or the slot no longer holds a valid entry.
+    pub fn get<S: ReadStorage>(
+        &self,
+        storage: &mut S,
+        index: usize,
+    ) -> Result<Option<(u32, [u8; N])>, S::Error> {
+        if index >= self.len {
+            return Ok(None);
+        }
+
+        let slot = (self.head + self.slots + 1 + index - self.len) % self.slots;
+        self.read_slot(storage, slot)
+    }
+
+    /// Read the newest entry with its sequence number.
+    #[inline]
+    pub fn last<S: ReadStorage>(
+        &self,
+        storage: &mut S,
+    ) -> Result<Option<(u32, [u8; N])>, S::Error> {
+        match self.len {
+            0 => Ok(None),
+            len => self.get(storage, len - 1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Eq)]
+    struct PowerLoss;
+
+    /// Erased EEPROM that counts writes and loses power once `bytes_left` more bytes have been
+    /// written.
+    struct Mem {
+        bytes: [u8; 64],
+        writes: usize,
+        bytes_left: usize,
+    }
+
+    impl Mem {
+        fn new() -> Self {
+            Self {
+                bytes: [0xFF; 64],
+                writes: 0,
+                bytes_left: usize::MAX,
+            }
+        }
+    }
+
+    impl ReadStorage for Mem {
+        type Error = PowerLoss;
+
+        fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), PowerLoss> {
+            let offset = offset as usize;
+            bytes.copy_from_slice(&self.bytes[offset..offset + bytes.len()]);
+            Ok(())
+        }
+
+        fn capacity(&self) -> usize {
+            self.bytes.len()
+        }
+    }
+
+    impl Storage for Mem {
+        fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), PowerLoss> {
+            self.writes += 1;
+            let n = bytes.len().min(self.bytes_left);
+            let offset = offset as usize;
+            self.bytes[offset..offset + n].copy_from_slice(&bytes[..n]);
+            self.bytes_left -= n;
+
+            match n == bytes.len() {
+                true => Ok(()),
+                false => Err(PowerLoss),
+            }
+        }
+    }
+
+    #[derive(Debug, Default, PartialEq, Eq)]
+    struct Counter(u32);
+
+    impl RecordData<4> for Counter {
+        const VERSION: u16 = 1;
+
+        fn to_bytes(&self) -> [u8; 4] {
+            self.0.to_le_bytes()
+        }
+
+        fn from_bytes(bytes: &[u8; 4]) -> Option<Self> {
+            Some(Counter(u32::from_le_bytes(*bytes)))
+        }
+    }
+
+    type CounterRecord = Record<Counter, 4>;
+
+    /// Offset of the data bytes in `slot` of a record at offset 0.
+    const fn record_data(slot: usize) -> usize {
+        slot * (CounterRecord::SIZE / 2) + RECORD_HEADER_SIZE
+    }
+
+    #[test]
+    fn record_without_copies_loads_default() {
+        let mut mem = Mem::new();
+        assert_eq!(CounterRecord::new(0).load(&mut mem), Ok(Counter::default()));
+    }
+
+    #[test]
+    fn record_alternates_slots() {
+        let mut mem = Mem::new();
+        let mut record = CounterRecord::new(0);
+        record.load(&mut mem).unwrap();
+        record.store(&mut mem, &Counter(1)).unwrap();
+        record.store(&mut mem, &Counter(2)).unwrap();
+
+        assert_eq!(mem.bytes[record_data(0)..][..4], 1u32.to_le_bytes());
+        assert_eq!(mem.bytes[record_data(1)..][..4], 2u32.to_le_bytes());
+        assert_eq!(CounterRecord::new(0).load(&mut mem), Ok(Counter(2)));
+
+        record.store(&mut mem, &Counter(3)).unwrap();
+        assert_eq!(mem.bytes[record_data(0)..][..4], 3u32.to_le_bytes());
+        assert_eq!(CounterRecord::new(0).load(&mut mem), Ok(Counter(3)));
+    }
+
+    #[test]
+    fn record_store_is_a_single_write() {
+        let mut mem = Mem::new();
+        CounterRecord::new(0).store(&mut mem, &Counter(1)).unwrap();
+        assert_eq!(mem.writes, 1);
+    }
+
+    #[test]
+    fn record_falls_back_to_older_copy() {
+        let mut mem = Mem::new();
+        let mut record = CounterRecord::new(0);
+        record.load(&mut mem).unwrap();
+        record.store(&mut mem, &Counter(1)).unwrap();
+        record.store(&mut mem, &Counter(2)).unwrap();
+
+        mem.bytes[record_data(1)] ^= 0x01;
+        assert_eq!(CounterRecord::new(0).load(&mut mem), Ok(Counter(1)));
+    }
+
+    #[test]
+    fn record_survives_torn_store() {
+        let mut mem = Mem::new();
+        let mut record = CounterRecord::new(0);
+        record.load(&mut mem).unwrap();
+        record.store(&mut mem, &Counter(1)).unwrap();
+        record.store(&mut mem, &Counter(2)).unwrap();
+
+        // The header lands but the data does not.
+        mem.bytes_left = RECORD_HEADER_SIZE;
+        assert_eq!(record.store(&mut mem, &Counter(3)), Err(PowerLoss));
+        assert_eq!(CounterRecord::new(0).load(&mut mem), Ok(Counter(2)));
+    }
+
+    #[test]
+    fn record_sequence_wraps() {
+        let mut mem = Mem::new();
+        let mut record = CounterRecord::new(0);
+        record.current = Some((u16::MAX - 1, 1));
+        record.store(&mut mem, &Counter(1)).unwrap();
+        record.store(&mut mem, &Counter(2)).unwrap();
+
+        assert_eq!(CounterRecord::new(0).load(&mut mem), Ok(Counter(2)));
+    }
+
+    /// A log of four 4-byte entries at offset 0.
+    fn new_log() -> RingLog<4> {
+        RingLog::new(0, 4 * RingLog::<4>::SLOT_SIZE)
+    }
+
+    #[test]
+    fn log_recovers_blank_storage_as_empty() {
+        let mut mem = Mem::new();
+        let mut log = new_log();
+        log.recover(&mut mem).unwrap();
+
+        assert!(log.is_empty());
+        assert_eq!(log.next_seq(), 0);
+        assert_eq!(log.last(&mut mem), Ok(None));
+    }
+
+    #[test]
+    fn log_get_and_last_follow_push_order() {
+        let mut mem = Mem::new();
+        let mut log = new_log();
+        for i in 0..3 {
+            assert_eq!(log.push(&mut mem, &[i; 4]), Ok(i as u32));
+        }
+
+        assert_eq!(log.len(), 3);
+        assert_eq!(log.get(&mut mem, 0), Ok(Some((0, [0; 4]))));
+        assert_eq!(log.get(&mut mem, 2), Ok(Some((2, [2; 4]))));
+        assert_eq!(log.get(&mut mem, 3), Ok(None));
+        assert_eq!(log.last(&mut mem), Ok(Some((2, [2; 4]))));
+    }
+
+    #[test]
+    fn log_recovers_after_wrap_around() {
+        let mut mem = Mem::new();
+        let mut log = new_log();
+        for i in 0..6 {
+            log.push(&mut mem, &[i; 4]).unwrap();
+        }
+
+        let mut recovered = new_log();
+        recovered.recover(&mut mem).unwrap();
+        assert_eq!(recovered.len(), 4);
+        assert_eq!(recovered.next_seq(), 6);
+        assert_eq!(recovered.get(&mut mem, 0), Ok(Some((2, [2; 4]))));
+        assert_eq!(recovered.last(&mut mem), Ok(Some((5, [5; 4]))));
+    }
+
+    #[test]
+    fn log_torn_push_ends_the_log() {
+        let mut mem = Mem::new();
+        let mut log = new_log();
+        for i in 0..6 {
+            log.push(&mut mem, &[i; 4]).unwrap();
+        }
+
+        // The sequence number and entry land over the oldest entry, but the CRC does not.
+        mem.bytes_left = 8;
+        mem.writes = 0;
+        assert_eq!(log.push(&mut mem, &[6; 4]), Err(PowerLoss));
+        assert_eq!(mem.writes, 1);
+
+        let mut recovered = new_log();
+        recovered.recover(&mut mem).unwrap();
+        assert_eq!(recovered.len(), 3);
+        assert_eq!(recovered.next_seq(), 6);
+        assert_eq!(recovered.get(&mut mem, 0), Ok(Some((3, [3; 4]))));
+        assert_eq!(recovered.last(&mut mem), Ok(Some((5, [5; 4]))));
+    }
+}
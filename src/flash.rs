@@ -1,11 +1,93 @@
 use embedded_storage::{ReadStorage, Storage, nor_flash};
 
-use crate::peripherals::{EEPROM, FLASH};
+mod firmware;
+pub mod kv;
 
-#[derive(Debug)]
+pub use firmware::{
+    BOOT_MAGIC, DFU_DETACH_MAGIC, FirmwareUpdater, FirmwareUpdaterConfig, SWAP_MAGIC, State,
+};
+
+use crate::{
+    peripherals::{EEPROM, FLASH},
+    rom::{self, IapError, IapResult},
+};
+
+/// Size of an EEPROM page. Accesses are split so that each IAP call covers at most one page.
+pub const EEPROM_PAGE_SIZE: usize = 64;
+
+/// Usable EEPROM size. The top 64 bytes of the 4 kB EEPROM are reserved.
+#[cfg(any(
+    feature = "lpc11u34",
+    feature = "lpc11u35",
+    feature = "lpc11u36",
+    feature = "lpc11u37"
+))]
+pub const EEPROM_SIZE: usize = 4096 - 64;
+#[cfg(not(any(
+    feature = "lpc11u34",
+    feature = "lpc11u35",
+    feature = "lpc11u36",
+    feature = "lpc11u37"
+)))]
+pub const EEPROM_SIZE: usize = 0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum EepromError {
+    /// The access extends past the usable EEPROM.
     OutOfRange,
+    /// The ROM rejected a source or destination address (`SRC_ADDR_ERROR`, `DST_ADDR_ERROR`).
+    AddressError,
+    /// A source or destination address is not mapped (`SRC_ADDR_NOT_MAPPED`,
+    /// `DST_ADDR_NOT_MAPPED`).
+    AddressNotMapped,
+    /// The ROM rejected the byte count (`COUNT_ERROR`).
+    CountError,
+    /// The EEPROM is busy (`BUSY`).
+    Busy,
+    /// The ROM does not know the command (`INVALID_COMMAND`).
+    InvalidCommand,
+    /// The ROM returned a flash status that does not apply to EEPROM accesses.
+    Unexpected(IapError),
+    /// The ROM returned a status code not documented in the user manual.
+    Unknown(u32),
+}
+
+impl EepromError {
+    /// Map an IAP status onto an [`EepromError`].
+    #[inline]
+    pub fn check<T>(result: IapResult<T>) -> Result<T, Self> {
+        match result.into_result() {
+            Ok(t) => Ok(t),
+            Err(IapResult::InvalidCommand) => Err(EepromError::InvalidCommand),
+            Err(IapResult::SrcAddrError | IapResult::DstAddrError) => {
+                Err(EepromError::AddressError)
+            }
+            Err(IapResult::SrcAddrNotMapped | IapResult::DstAddrNotMapped) => {
+                Err(EepromError::AddressNotMapped)
+            }
+            Err(IapResult::CountError) => Err(EepromError::CountError),
+            Err(IapResult::Busy) => Err(EepromError::Busy),
+            Err(IapResult::Unknown(code)) => Err(EepromError::Unknown(code)),
+            Err(err) => Err(EepromError::Unexpected(err)),
+        }
+    }
+}
+
+/// Split the access at `offset` into chunks that do not cross an EEPROM page.
+#[inline]
+fn eeprom_chunks(offset: u32, len: usize) -> impl Iterator<Item = (u32, core::ops::Range<usize>)> {
+    let mut pos = 0;
+    core::iter::from_fn(move || {
+        if pos == len {
+            return None;
+        }
+        let addr = offset as usize + pos;
+        let n = (EEPROM_PAGE_SIZE - addr % EEPROM_PAGE_SIZE).min(len - pos);
+        let range = pos..pos + n;
+        pos += n;
+        Some((addr as u32, range))
+    })
 }
 
 impl ReadStorage for EEPROM {
@@ -16,44 +98,138 @@ impl ReadStorage for EEPROM {
             return Err(EepromError::OutOfRange);
         }
 
-        todo!()
+        for (addr, range) in eeprom_chunks(offset, bytes.len()) {
+            EepromError::check(rom::eeprom_read(addr, &mut bytes[range]))?;
+        }
+        Ok(())
     }
 
     #[inline]
     fn capacity(&self) -> usize {
-        #[cfg(any(
-            feature = "lpc11u34",
-            feature = "lpc11u35",
-            feature = "lpc11u36",
-            feature = "lpc11u37"
-        ))]
-        return 4096;
-
-        #[cfg(not(any(
-            feature = "lpc11u34",
-            feature = "lpc11u35",
-            feature = "lpc11u36",
-            feature = "lpc11u37"
-        )))]
-        return 0;
+        EEPROM_SIZE
     }
 }
 
 impl Storage for EEPROM {
     fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
-        todo!()
+        if offset as usize + bytes.len() > self.capacity() {
+            return Err(EepromError::OutOfRange);
+        }
+
+        for (addr, range) in eeprom_chunks(offset, bytes.len()) {
+            EepromError::check(rom::eeprom_write(addr, &bytes[range]))?;
+        }
+        Ok(())
     }
 }
 
-#[derive(Debug)]
+/// Size of the on-chip flash.
+#[cfg(feature = "lpc11u34")]
+pub const FLASH_SIZE: usize = 40 * 1024;
+#[cfg(feature = "lpc11u35")]
+pub const FLASH_SIZE: usize = 64 * 1024;
+#[cfg(feature = "lpc11u36")]
+pub const FLASH_SIZE: usize = 96 * 1024;
+#[cfg(feature = "lpc11u37")]
+pub const FLASH_SIZE: usize = 128 * 1024;
+#[cfg(not(any(
+    feature = "lpc11u34",
+    feature = "lpc11u35",
+    feature = "lpc11u36",
+    feature = "lpc11u37"
+)))]
+pub const FLASH_SIZE: usize = 0;
+
+/// Size of a flash sector, the unit erased by [`rom::erase_sectors`].
+pub const SECTOR_SIZE: usize = 4096;
+
+/// Size of a flash page, the smallest unit programmed by [`rom::copy_ram_to_flash`].
+pub const PAGE_SIZE: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum FlashError {
-    // TODO
+    /// The offset or length is not a multiple of the read, write or erase size.
+    NotAligned,
+    /// The operation extends past the end of the flash.
+    OutOfBounds,
+    /// The ROM rejected a source or destination address (`SRC_ADDR_ERROR`, `DST_ADDR_ERROR`).
+    AddressError,
+    /// A source or destination address is not mapped (`SRC_ADDR_NOT_MAPPED`,
+    /// `DST_ADDR_NOT_MAPPED`).
+    AddressNotMapped,
+    /// The ROM rejected the byte count (`COUNT_ERROR`).
+    CountError,
+    /// The ROM rejected the sector number (`INVALID_SECTOR`).
+    InvalidSector,
+    /// A sector was not blank at `offset` (`SECTOR_NOT_BLANK`).
+    NotBlank { offset: usize },
+    /// The sector was not prepared before writing (`SECTOR_NOT_PREPARED_FOR_WRITE_OPERATION`).
+    NotPrepared,
+    /// Flash contents differ at `offset` (`COMPARE_ERROR`).
+    CompareMismatch { offset: usize },
+    /// The flash interface is busy (`BUSY`).
+    Busy,
+    /// The ROM does not know the command (`INVALID_COMMAND`).
+    InvalidCommand,
+    /// The ROM returned an undocumented status code.
+    Unknown(u32),
+}
+
+impl FlashError {
+    /// Map an IAP status onto a [`FlashError`].
+    #[inline]
+    pub fn check<T>(result: IapResult<T>) -> Result<T, Self> {
+        match result {
+            IapResult::Success(t) => Ok(t),
+            IapResult::InvalidCommand => Err(FlashError::InvalidCommand),
+            IapResult::SrcAddrError | IapResult::DstAddrError => Err(FlashError::AddressError),
+            IapResult::SrcAddrNotMapped | IapResult::DstAddrNotMapped => {
+                Err(FlashError::AddressNotMapped)
+            }
+            IapResult::CountError => Err(FlashError::CountError),
+            IapResult::InvalidSector => Err(FlashError::InvalidSector),
+            IapResult::SectorNotBlank { first_offset, .. } => Err(FlashError::NotBlank {
+                offset: first_offset,
+            }),
+            IapResult::SectorNotPreparedForWriteOperation => Err(FlashError::NotPrepared),
+            IapResult::CompareError { first_offset } => Err(FlashError::CompareMismatch {
+                offset: first_offset,
+            }),
+            IapResult::Busy => Err(FlashError::Busy),
+            IapResult::Unknown(code) => Err(FlashError::Unknown(code)),
+        }
+    }
+}
+
+impl From<nor_flash::NorFlashErrorKind> for FlashError {
+    #[inline]
+    fn from(kind: nor_flash::NorFlashErrorKind) -> Self {
+        match kind {
+            nor_flash::NorFlashErrorKind::NotAligned => FlashError::NotAligned,
+            _ => FlashError::OutOfBounds,
+        }
+    }
 }
 
 impl nor_flash::NorFlashError for FlashError {
     fn kind(&self) -> nor_flash::NorFlashErrorKind {
-        todo!()
+        use nor_flash::NorFlashErrorKind;
+
+        match self {
+            FlashError::NotAligned | FlashError::AddressError | FlashError::CountError => {
+                NorFlashErrorKind::NotAligned
+            }
+            FlashError::OutOfBounds | FlashError::AddressNotMapped | FlashError::InvalidSector => {
+                NorFlashErrorKind::OutOfBounds
+            }
+            FlashError::NotBlank { .. }
+            | FlashError::NotPrepared
+            | FlashError::CompareMismatch { .. }
+            | FlashError::Busy
+            | FlashError::InvalidCommand
+            | FlashError::Unknown(_) => NorFlashErrorKind::Other,
+        }
     }
 }
 
@@ -62,28 +238,175 @@ impl nor_flash::ErrorType for FLASH {
 }
 
 impl nor_flash::ReadNorFlash for FLASH {
-    const READ_SIZE: usize = 4;
+    const READ_SIZE: usize = 1;
 
     fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
-        todo!()
+        nor_flash::check_read(self, offset, bytes.len())?;
+
+        // Flash is mapped at address 0.
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                offset as usize as *const u8,
+                bytes.as_mut_ptr(),
+                bytes.len(),
+            );
+        }
+        Ok(())
     }
 
+    #[inline]
     fn capacity(&self) -> usize {
-        todo!()
+        FLASH_SIZE
     }
 }
 
 impl nor_flash::NorFlash for FLASH {
-    const WRITE_SIZE: usize = 0;
+    const WRITE_SIZE: usize = PAGE_SIZE;
 
-    const ERASE_SIZE: usize = 0;
+    const ERASE_SIZE: usize = SECTOR_SIZE;
 
     fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
-        todo!()
+        nor_flash::check_erase(self, from, to)?;
+        if from == to {
+            return Ok(());
+        }
+
+        let first = from / SECTOR_SIZE as u32;
+        let last = to / SECTOR_SIZE as u32 - 1;
+
+        critical_section::with(|_| {
+            FlashError::check(rom::prepare_sectors_for_write(first, last))?;
+            FlashError::check(unsafe { rom::erase_sectors(first, last, rom::cclk_khz()) })
+        })
     }
 
     fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
-        todo!()
+        nor_flash::check_write(self, offset, bytes.len())?;
+
+        // The ROM copies from word-aligned RAM only.
+        let mut buf = [0u32; PAGE_SIZE / 4];
+
+        for (i, chunk) in bytes.chunks_exact(PAGE_SIZE).enumerate() {
+            for (word, src) in buf.iter_mut().zip(chunk.chunks_exact(4)) {
+                *word = u32::from_le_bytes([src[0], src[1], src[2], src[3]]);
+            }
+
+            let dst = offset + (i * PAGE_SIZE) as u32;
+            let sector = dst / SECTOR_SIZE as u32;
+
+            critical_section::with(|_| {
+                FlashError::check(rom::prepare_sectors_for_write(sector, sector))?;
+                FlashError::check(unsafe {
+                    rom::copy_ram_to_flash(
+                        dst as usize as *mut (),
+                        buf.as_ptr() as *const (),
+                        PAGE_SIZE as u32,
+                        rom::cclk_khz(),
+                    )
+                })
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reprogramming a page only clears bits: the LPC11U3x flash has no ECC to invalidate.
+impl nor_flash::MultiwriteNorFlash for FLASH {}
+
+/// View of the flash erased in 256-byte pages instead of 4 kB sectors.
+#[cfg(any(
+    feature = "lpc11u34",
+    feature = "lpc11u35",
+    feature = "lpc11u36",
+    feature = "lpc11u37"
+))]
+pub struct FlashPages<'a> {
+    flash: &'a mut FLASH,
+}
+
+#[cfg(any(
+    feature = "lpc11u34",
+    feature = "lpc11u35",
+    feature = "lpc11u36",
+    feature = "lpc11u37"
+))]
+impl FLASH {
+    /// Borrow the flash with page erase granularity.
+    #[inline]
+    pub fn pages(&mut self) -> FlashPages<'_> {
+        FlashPages { flash: self }
+    }
+}
+
+#[cfg(any(
+    feature = "lpc11u34",
+    feature = "lpc11u35",
+    feature = "lpc11u36",
+    feature = "lpc11u37"
+))]
+impl nor_flash::ErrorType for FlashPages<'_> {
+    type Error = FlashError;
+}
+
+#[cfg(any(
+    feature = "lpc11u34",
+    feature = "lpc11u35",
+    feature = "lpc11u36",
+    feature = "lpc11u37"
+))]
+impl nor_flash::ReadNorFlash for FlashPages<'_> {
+    const READ_SIZE: usize = <FLASH as nor_flash::ReadNorFlash>::READ_SIZE;
+
+    #[inline]
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        nor_flash::ReadNorFlash::read(self.flash, offset, bytes)
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        FLASH_SIZE
+    }
+}
+
+#[cfg(any(
+    feature = "lpc11u34",
+    feature = "lpc11u35",
+    feature = "lpc11u36",
+    feature = "lpc11u37"
+))]
+impl nor_flash::NorFlash for FlashPages<'_> {
+    const WRITE_SIZE: usize = PAGE_SIZE;
+
+    const ERASE_SIZE: usize = PAGE_SIZE;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        nor_flash::check_erase(self, from, to)?;
+        if from == to {
+            return Ok(());
+        }
+
+        let first_page = from / PAGE_SIZE as u32;
+        let last_page = to / PAGE_SIZE as u32 - 1;
+        let first_sector = from / SECTOR_SIZE as u32;
+        let last_sector = (to - 1) / SECTOR_SIZE as u32;
+
+        critical_section::with(|_| {
+            FlashError::check(rom::prepare_sectors_for_write(first_sector, last_sector))?;
+            FlashError::check(unsafe { rom::erase_page(first_page, last_page, rom::cclk_khz()) })
+        })
+    }
+
+    #[inline]
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        nor_flash::NorFlash::write(self.flash, offset, bytes)
     }
 }
 
+#[cfg(any(
+    feature = "lpc11u34",
+    feature = "lpc11u35",
+    feature = "lpc11u36",
+    feature = "lpc11u37"
+))]
+impl nor_flash::MultiwriteNorFlash for FlashPages<'_> {}
@@ -0,0 +1,247 @@
+//! Firmware updates in the partition layout of `embassy-boot`.
+//!
+//! The flash is split into an ACTIVE partition holding the running firmware, a DFU partition
+//! receiving the new image (at least one sector larger than ACTIVE), and a STATE partition. The
+//! bootloader swaps ACTIVE and DFU when the STATE magic requests it, and swaps them back on the
+//! next boot unless the new firmware marks itself booted.
+//!
+//! The partitions are taken from the linker symbols used by `embassy-boot`:
+//!
+//! ```text
+//! __bootloader_state_start = ORIGIN(BOOTLOADER_STATE);
+//! __bootloader_state_end = ORIGIN(BOOTLOADER_STATE) + LENGTH(BOOTLOADER_STATE);
+//! __bootloader_dfu_start = ORIGIN(DFU);
+//! __bootloader_dfu_end = ORIGIN(DFU) + LENGTH(DFU);
+//! ```
+//!
+//! The bootloader must be built with the same [`FLASH`] implementation, so both agree on the
+//! write size the STATE magic is repeated over.
+
+use core::ops::Range;
+
+use embedded_storage::nor_flash::{NorFlash, ReadNorFlash};
+
+use crate::{peripherals::FLASH, rom};
+
+use super::{FlashError, PAGE_SIZE, SECTOR_SIZE};
+
+/// STATE magic of a confirmed firmware.
+pub const BOOT_MAGIC: u8 = 0xD0;
+/// STATE magic requesting the bootloader to swap in the DFU partition.
+pub const SWAP_MAGIC: u8 = 0xF0;
+/// STATE magic requesting the bootloader to enter DFU mode.
+pub const DFU_DETACH_MAGIC: u8 = 0xE0;
+
+/// Bootloader state, as recorded in the STATE partition.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum State {
+    /// The running firmware has been confirmed, or no update was requested.
+    Boot,
+    /// An update was requested, or the running firmware was just swapped in and has not been
+    /// confirmed with [`FirmwareUpdater::mark_booted`].
+    Swap,
+    /// DFU mode was requested.
+    DfuDetach,
+}
+
+/// DFU and STATE partitions, as offsets into the flash.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FirmwareUpdaterConfig {
+    pub dfu: Range<u32>,
+    pub state: Range<u32>,
+}
+
+impl FirmwareUpdaterConfig {
+    /// Partitions from the `__bootloader_{dfu,state}_{start,end}` linker symbols.
+    pub fn from_linkerfile() -> Self {
+        unsafe extern "C" {
+            static __bootloader_state_start: u8;
+            static __bootloader_state_end: u8;
+            static __bootloader_dfu_start: u8;
+            static __bootloader_dfu_end: u8;
+        }
+
+        let addr = |sym: *const u8| sym as usize as u32;
+        Self {
+            dfu: addr(core::ptr::addr_of!(__bootloader_dfu_start))
+                ..addr(core::ptr::addr_of!(__bootloader_dfu_end)),
+            state: addr(core::ptr::addr_of!(__bootloader_state_start))
+                ..addr(core::ptr::addr_of!(__bootloader_state_end)),
+        }
+    }
+}
+
+/// Writes a new image into the DFU partition and requests the bootloader to swap it in.
+pub struct FirmwareUpdater<'a> {
+    flash: &'a mut FLASH,
+    config: FirmwareUpdaterConfig,
+    /// Sectors erased since the update started, which are not erased again.
+    erased: Range<u32>,
+}
+
+impl<'a> FirmwareUpdater<'a> {
+    /// # Panics
+    /// Panics if a partition is not sector aligned.
+    pub fn new(flash: &'a mut FLASH, config: FirmwareUpdaterConfig) -> Self {
+        for range in [&config.dfu, &config.state] {
+            assert!(
+                range.start.is_multiple_of(SECTOR_SIZE as u32)
+                    && range.end.is_multiple_of(SECTOR_SIZE as u32)
+            );
+        }
+        Self {
+            flash,
+            config,
+            erased: 0..0,
+        }
+    }
+
+    /// Current bootloader state.
+    pub fn get_state(&mut self) -> Result<State, FlashError> {
+        let mut magic = [0u8; PAGE_SIZE];
+        self.flash.read(self.config.state.start, &mut magic)?;
+
+        Ok(if magic.iter().all(|&b| b == SWAP_MAGIC) {
+            State::Swap
+        } else if magic.iter().all(|&b| b == DFU_DETACH_MAGIC) {
+            State::DfuDetach
+        } else {
+            State::Boot
+        })
+    }
+
+    /// Erase the whole DFU partition, ahead of writing an image out of order.
+    pub fn prepare_update(&mut self) -> Result<(), FlashError> {
+        self.flash
+            .erase(self.config.dfu.start, self.config.dfu.end)?;
+        self.erased = self.config.dfu.clone();
+        Ok(())
+    }
+
+    /// Write `data` at `offset` into the DFU partition and verify it.
+    ///
+    /// `offset` must be a multiple of [`PAGE_SIZE`]; a final partial page is padded with 0xFF.
+    /// Unless [`prepare_update`][Self::prepare_update] was called, each sector is erased the first
+    /// time it is written to, so the image must be written one sector after the other; pages
+    /// within a sector may come in any order.
+    pub fn write_firmware(&mut self, offset: u32, data: &[u8]) -> Result<(), FlashError> {
+        if !offset.is_multiple_of(PAGE_SIZE as u32) {
+            return Err(FlashError::NotAligned);
+        }
+        let start = self.config.dfu.start + offset;
+        let end = start + data.len().next_multiple_of(PAGE_SIZE) as u32;
+        if end > self.config.dfu.end {
+            return Err(FlashError::OutOfBounds);
+        }
+
+        for sector in unerased_sectors(self.erased.clone(), start..end) {
+            self.flash.erase(sector, sector + SECTOR_SIZE as u32)?;
+            self.erased = sector..sector + SECTOR_SIZE as u32;
+        }
+
+        // Pages are staged in word-aligned RAM for both the write and the ROM compare.
+        let mut page = [0u32; PAGE_SIZE / 4];
+        for (i, chunk) in data.chunks(PAGE_SIZE).enumerate() {
+            let bytes: &mut [u8; PAGE_SIZE] = page_bytes(&mut page);
+            bytes.fill(0xFF);
+            bytes[..chunk.len()].copy_from_slice(chunk);
+
+            let dst = start + (i * PAGE_SIZE) as u32;
+            self.flash.write(dst, bytes)?;
+            FlashError::check(rom::compare(
+                dst as usize as *const u32,
+                page.as_ptr(),
+                PAGE_SIZE as u32,
+            ))
+            .map_err(|e| match e {
+                FlashError::CompareMismatch { offset } => FlashError::CompareMismatch {
+                    offset: offset + (dst - self.config.dfu.start) as usize,
+                },
+                e => e,
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Request the bootloader to swap in the DFU partition on the next reset.
+    pub fn mark_updated(&mut self) -> Result<(), FlashError> {
+        self.set_magic(SWAP_MAGIC)
+    }
+
+    /// Request the bootloader to enter DFU mode on the next reset.
+    pub fn mark_dfu(&mut self) -> Result<(), FlashError> {
+        self.set_magic(DFU_DETACH_MAGIC)
+    }
+
+    /// Confirm the running firmware, so the bootloader does not revert it.
+    pub fn mark_booted(&mut self) -> Result<(), FlashError> {
+        self.set_magic(BOOT_MAGIC)
+    }
+
+    /// Erase the STATE partition, clearing any swap progress, and fill the first write unit
+    /// with `magic`.
+    fn set_magic(&mut self, magic: u8) -> Result<(), FlashError> {
+        let mut buf = [0u8; PAGE_SIZE];
+        self.flash.read(self.config.state.start, &mut buf)?;
+        if buf.iter().all(|&b| b == magic) {
+            return Ok(());
+        }
+
+        self.flash
+            .erase(self.config.state.start, self.config.state.end)?;
+        buf.fill(magic);
+        self.flash.write(self.config.state.start, &buf)
+    }
+}
+
+/// Sectors overlapping `range` that are not within `erased`.
+fn unerased_sectors(erased: Range<u32>, range: Range<u32>) -> impl Iterator<Item = u32> {
+    let first = range.start / SECTOR_SIZE as u32 * SECTOR_SIZE as u32;
+    (first..range.end)
+        .step_by(SECTOR_SIZE)
+        .filter(move |sector| !erased.contains(sector))
+}
+
+#[inline]
+fn page_bytes(page: &mut [u32; PAGE_SIZE / 4]) -> &mut [u8; PAGE_SIZE] {
+    // SAFETY: same size, and u8 has no alignment or validity requirements.
+    unsafe { &mut *(page as *mut [u32; PAGE_SIZE / 4] as *mut [u8; PAGE_SIZE]) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DFU: Range<u32> = 0x1_0000..0x1_8000;
+
+    /// Sectors erased by a write of `len` bytes at `offset`, as offsets into the DFU partition.
+    fn sectors(erased: Range<u32>, offset: u32, len: u32) -> impl Iterator<Item = u32> {
+        let start = DFU.start + offset;
+        unerased_sectors(erased, start..start + len).map(|sector| sector - DFU.start)
+    }
+
+    #[test]
+    fn in_order_erases_each_sector_once() {
+        assert!(sectors(0..0, 0, 256).eq([0]));
+        let erased = DFU.start..DFU.start + 0x1000;
+        assert!(sectors(erased.clone(), 256, 256).eq([]));
+        assert!(sectors(erased.clone(), 0xF00, 0x200).eq([0x1000]));
+    }
+
+    #[test]
+    fn out_of_order_within_a_sector_keeps_earlier_pages() {
+        assert!(sectors(0..0, 4352, 256).eq([4096]));
+        let erased = DFU.start + 4096..DFU.start + 8192;
+        assert!(sectors(erased.clone(), 4096, 256).eq([]));
+    }
+
+    #[test]
+    fn nothing_is_erased_after_prepare_update() {
+        assert!(sectors(DFU, 4352, 256).eq([]));
+        assert!(sectors(DFU, 4096, 0x2000).eq([]));
+        assert!(sectors(DFU, 0, 256).eq([]));
+    }
+}
@@ -0,0 +1,749 @@
+//! Log-structured key-value store over a range of flash sectors.
+//!
+//! Values are appended as records; the newest valid record for a key wins. When the active sector
+//! is full, the next sector is opened. One sector is always kept erased so the oldest sector can
+//! be garbage collected: its live records are copied into the erased sector, and it is erased
+//! in turn. A power loss at any point leaves either the old or the new copy of every record, and
+//! an interrupted collection is finished by [`KvStore::repair`] or the next write.
+//!
+//! The sectors should be reserved in `memory.x`, so the program can never be linked into them:
+//!
+//! ```text
+//! MEMORY
+//! {
+//!   FLASH : ORIGIN = 0x00000000, LENGTH = 112K
+//!   KV    : ORIGIN = 0x0001C000, LENGTH = 16K
+//!   RAM   : ORIGIN = 0x10000000, LENGTH = 8K
+//! }
+//!
+//! __kv_start = ORIGIN(KV);
+//! __kv_end = ORIGIN(KV) + LENGTH(KV);
+//! ```
+//!
+//! and the range passed on with [`reserved_range`]:
+//!
+//! ```rust,ignore
+//! let mut kv = KvStore::<_>::new(&mut *p.FLASH, kv::reserved_range())?;
+//! ```
+
+use core::ops::Range;
+
+use embedded_storage::nor_flash::NorFlash;
+
+use crate::eeprom::crc16;
+
+/// Range of flash between the `__kv_start` and `__kv_end` linker symbols.
+pub fn reserved_range() -> Range<u32> {
+    unsafe extern "C" {
+        static __kv_start: u8;
+        static __kv_end: u8;
+    }
+
+    let start = core::ptr::addr_of!(__kv_start) as usize as u32;
+    let end = core::ptr::addr_of!(__kv_end) as usize as u32;
+    start..end
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum KvError<E> {
+    /// The underlying flash returned an error.
+    Flash(E),
+    /// `u32::MAX` is reserved to mark free space.
+    InvalidKey,
+    /// The value does not fit in a single sector.
+    TooLarge,
+    /// The buffer passed to [`KvStore::get`] is shorter than the value.
+    BufferTooSmall,
+    /// The live records fill the store, even after garbage collection.
+    Full,
+}
+
+impl<E> From<E> for KvError<E> {
+    #[inline]
+    fn from(e: E) -> Self {
+        KvError::Flash(e)
+    }
+}
+
+const SECTOR_MAGIC: u32 = 0x4B56_5331;
+const SECTOR_HEADER_SIZE: u32 = 8;
+const RECORD_HEADER_SIZE: u32 = 8;
+const ERASED_KEY: u32 = u32::MAX;
+const ERASED_LEN: u16 = u16::MAX;
+const TOMBSTONE_LEN: u16 = u16::MAX - 1;
+
+/// Header of a record: key, length (or [`TOMBSTONE_LEN`]) and CRC-16 over both and the data.
+#[derive(Clone, Copy)]
+struct Record {
+    offset: u32,
+    key: u32,
+    len: u16,
+    crc: u16,
+}
+
+impl Record {
+    #[inline]
+    const fn data_len(&self) -> u32 {
+        match self.len {
+            TOMBSTONE_LEN => 0,
+            len => len as u32,
+        }
+    }
+
+    #[inline]
+    fn header_bytes(&self) -> [u8; 6] {
+        let mut header = [0u8; 6];
+        header[..4].copy_from_slice(&self.key.to_le_bytes());
+        header[4..].copy_from_slice(&self.len.to_le_bytes());
+        header
+    }
+}
+
+/// A key-value store over the sectors of `F` within a range.
+///
+/// The sector header and every record are padded to `F::WRITE_SIZE`, so with
+/// [`FLASH`][crate::peripherals::FLASH] and its 256-byte pages, a 4 KB sector holds at most 15
+/// records. They are written through a `BUF`-byte RAM buffer, which must be a multiple of
+/// `F::WRITE_SIZE`. `F::READ_SIZE` must be 1, as with `FLASH`.
+pub struct KvStore<'a, F: NorFlash, const BUF: usize = 256> {
+    flash: &'a mut F,
+    start: u32,
+    sectors: u32,
+    /// Active sector, its generation and the offset of its free space.
+    active: Option<(u32, u32, u32)>,
+}
+
+impl<'a, F: NorFlash, const BUF: usize> KvStore<'a, F, BUF> {
+    /// Mount the store over `range`, which must cover at least two whole erase sectors.
+    ///
+    /// This only reads the flash; see [`repair`][Self::repair].
+    ///
+    /// # Panics
+    /// Panics if `range` is not sector aligned or too small, or if `BUF` does not suit `F`.
+    pub fn new(flash: &'a mut F, range: Range<u32>) -> Result<Self, KvError<F::Error>> {
+        assert!(F::READ_SIZE == 1);
+        assert!(BUF >= F::WRITE_SIZE && BUF.is_multiple_of(F::WRITE_SIZE));
+        assert!(BUF >= (SECTOR_HEADER_SIZE + RECORD_HEADER_SIZE) as usize);
+        assert!(
+            range.start.is_multiple_of(F::ERASE_SIZE as u32)
+                && range.end.is_multiple_of(F::ERASE_SIZE as u32)
+        );
+
+        let sectors = (range.end - range.start) / F::ERASE_SIZE as u32;
+        assert!(sectors >= 2);
+
+        let mut store = Self {
+            flash,
+            start: range.start,
+            sectors,
+            active: None,
+        };
+
+        let mut newest: Option<(u32, u32)> = None;
+        for sector in 0..sectors {
+            if let Some(generation) = store.sector_generation(sector)?
+                && newest.is_none_or(|(_, newest)| generation > newest)
+            {
+                newest = Some((sector, generation));
+            }
+        }
+
+        if let Some((sector, generation)) = newest {
+            // Nothing may be programmed over a torn write, so the next write opens a new sector.
+            let offset = match store.free_space(sector)? {
+                (_, true) => store.sector_end(sector),
+                (offset, false) => offset,
+            };
+            store.active = Some((sector, generation, offset));
+        }
+
+        Ok(store)
+    }
+
+    /// Finish a garbage collection cut short by a power loss, if there is one.
+    ///
+    /// This erases and programs flash. [`insert`][Self::insert] and [`remove`][Self::remove] call
+    /// it first, so calling it after mounting only moves the work out of the next write.
+    pub fn repair(&mut self) -> Result<(), KvError<F::Error>> {
+        let Some((active, generation, _)) = self.active else {
+            return Ok(());
+        };
+        // Outside a collection, a sector is always kept erased.
+        let Some(oldest) = self.oldest_if_none_erased()? else {
+            return Ok(());
+        };
+        if oldest == active {
+            return Ok(());
+        }
+
+        // The active sector only holds copies from `oldest`, so a torn copy is redone from scratch.
+        if self.free_space(active)?.1 {
+            self.open_sector(active, generation)?;
+        }
+        self.collect(oldest)
+    }
+
+    /// Read the value of `key` into `buf`, returning its length, or `None` if it is not set.
+    pub fn get(&mut self, key: u32, buf: &mut [u8]) -> Result<Option<usize>, KvError<F::Error>> {
+        let Some((_, record)) = self.latest(key)? else {
+            return Ok(None);
+        };
+        if record.len == TOMBSTONE_LEN {
+            return Ok(None);
+        }
+
+        let len = record.data_len() as usize;
+        if buf.len() < len {
+            return Err(KvError::BufferTooSmall);
+        }
+        self.flash
+            .read(record.offset + RECORD_HEADER_SIZE, &mut buf[..len])?;
+        Ok(Some(len))
+    }
+
+    /// Set `key` to `value`.
+    pub fn insert(&mut self, key: u32, value: &[u8]) -> Result<(), KvError<F::Error>> {
+        if key == ERASED_KEY {
+            return Err(KvError::InvalidKey);
+        }
+        if value.len() >= TOMBSTONE_LEN as usize
+            || self.record_size(value.len() as u32)
+                > F::ERASE_SIZE as u32 - Self::align(SECTOR_HEADER_SIZE)
+        {
+            return Err(KvError::TooLarge);
+        }
+
+        let record = Record {
+            offset: 0,
+            key,
+            len: value.len() as u16,
+            crc: 0,
+        };
+        self.append(record, value)
+    }
+
+    /// Remove `key`, if it is set.
+    pub fn remove(&mut self, key: u32) -> Result<(), KvError<F::Error>> {
+        if key == ERASED_KEY {
+            return Err(KvError::InvalidKey);
+        }
+        match self.latest(key)? {
+            Some((_, record)) if record.len != TOMBSTONE_LEN => {}
+            _ => return Ok(()),
+        }
+
+        let record = Record {
+            offset: 0,
+            key,
+            len: TOMBSTONE_LEN,
+            crc: 0,
+        };
+        self.append(record, &[])
+    }
+
+    #[inline]
+    const fn sector_start(&self, sector: u32) -> u32 {
+        self.start + sector * F::ERASE_SIZE as u32
+    }
+
+    #[inline]
+    const fn sector_end(&self, sector: u32) -> u32 {
+        self.sector_start(sector + 1)
+    }
+
+    #[inline]
+    const fn align(n: u32) -> u32 {
+        n.div_ceil(F::WRITE_SIZE as u32) * F::WRITE_SIZE as u32
+    }
+
+    #[inline]
+    const fn first_record(&self, sector: u32) -> u32 {
+        self.sector_start(sector) + Self::align(SECTOR_HEADER_SIZE)
+    }
+
+    #[inline]
+    const fn record_size(&self, data_len: u32) -> u32 {
+        Self::align(RECORD_HEADER_SIZE + data_len)
+    }
+
+    #[inline]
+    const fn next_offset(&self, record: &Record) -> u32 {
+        record.offset + self.record_size(record.data_len())
+    }
+
+    fn read_u32s(&mut self, offset: u32) -> Result<[u32; 2], F::Error> {
+        let mut bytes = [0u8; 8];
+        self.flash.read(offset, &mut bytes)?;
+        Ok([
+            u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+        ])
+    }
+
+    /// Generation of `sector`, or `None` if it is not part of the store.
+    fn sector_generation(&mut self, sector: u32) -> Result<Option<u32>, F::Error> {
+        let [generation, magic] = self.read_u32s(self.sector_start(sector))?;
+        Ok((magic == SECTOR_MAGIC).then_some(generation))
+    }
+
+    /// Offset of the free space in `sector`, and whether any of it is programmed, as a torn write
+    /// leaves it.
+    fn free_space(&mut self, sector: u32) -> Result<(u32, bool), F::Error> {
+        let mut offset = self.first_record(sector);
+        while let Some(record) = self.record_at(sector, offset)? {
+            offset = self.next_offset(&record);
+        }
+
+        let end = self.sector_end(sector);
+        let mut chunk = [0u8; 32];
+        let mut pos = offset;
+        while pos < end {
+            let n = (end - pos).min(chunk.len() as u32) as usize;
+            self.flash.read(pos, &mut chunk[..n])?;
+            if chunk[..n].iter().any(|&b| b != 0xFF) {
+                return Ok((offset, true));
+            }
+            pos += n as u32;
+        }
+        Ok((offset, false))
+    }
+
+    /// Sector with the lowest generation, or `None` if any sector is erased.
+    fn oldest_if_none_erased(&mut self) -> Result<Option<u32>, F::Error> {
+        let mut oldest: Option<(u32, u32)> = None;
+        for sector in 0..self.sectors {
+            let Some(generation) = self.sector_generation(sector)? else {
+                return Ok(None);
+            };
+            if oldest.is_none_or(|(_, oldest)| generation < oldest) {
+                oldest = Some((sector, generation));
+            }
+        }
+        Ok(oldest.map(|(sector, _)| sector))
+    }
+
+    /// Header of the record at `offset` in `sector`, or `None` at the start of free space.
+    ///
+    /// A header that does not fit the sector is treated as the end of the sector.
+    fn record_at(&mut self, sector: u32, offset: u32) -> Result<Option<Record>, F::Error> {
+        if offset + RECORD_HEADER_SIZE > self.sector_end(sector) {
+            return Ok(None);
+        }
+
+        let [key, len_crc] = self.read_u32s(offset)?;
+        let record = Record {
+            offset,
+            key,
+            len: len_crc as u16,
+            crc: (len_crc >> 16) as u16,
+        };
+
+        if key == ERASED_KEY && record.len == ERASED_LEN {
+            return Ok(None);
+        }
+        if self.next_offset(&record) > self.sector_end(sector) {
+            // A torn header; nothing after it can be trusted.
+            return Ok(None);
+        }
+        Ok(Some(record))
+    }
+
+    /// Whether the data of `record` matches its CRC.
+    fn is_valid(&mut self, record: &Record) -> Result<bool, F::Error> {
+        let mut crc = crc16(0xFFFF, &record.header_bytes());
+        let mut chunk = [0u8; 32];
+        let mut pos = 0;
+        while pos < record.data_len() {
+            let n = (record.data_len() - pos).min(chunk.len() as u32) as usize;
+            self.flash
+                .read(record.offset + RECORD_HEADER_SIZE + pos, &mut chunk[..n])?;
+            crc = crc16(crc, &chunk[..n]);
+            pos += n as u32;
+        }
+        Ok(crc == record.crc)
+    }
+
+    /// Newest valid record for `key`, with the generation of its sector.
+    fn latest(&mut self, key: u32) -> Result<Option<(u32, Record)>, F::Error> {
+        let mut latest: Option<(u32, Record)> = None;
+
+        for sector in 0..self.sectors {
+            let Some(generation) = self.sector_generation(sector)? else {
+                continue;
+            };
+            if latest.is_some_and(|(latest, _)| latest > generation) {
+                continue;
+            }
+
+            let mut offset = self.first_record(sector);
+            while let Some(record) = self.record_at(sector, offset)? {
+                offset = self.next_offset(&record);
+                if record.key == key && self.is_valid(&record)? {
+                    latest = Some((generation, record));
+                }
+            }
+        }
+
+        Ok(latest)
+    }
+
+    /// Erase `sector` and make it the active sector with `generation`.
+    fn open_sector(&mut self, sector: u32, generation: u32) -> Result<(), F::Error> {
+        self.flash
+            .erase(self.sector_start(sector), self.sector_end(sector))?;
+
+        let mut buf = [0xFFu8; BUF];
+        // The magic comes last, so a torn header does not make the sector part of the store.
+        buf[..4].copy_from_slice(&generation.to_le_bytes());
+        buf[4..8].copy_from_slice(&SECTOR_MAGIC.to_le_bytes());
+        self.flash.write(
+            self.sector_start(sector),
+            &buf[..Self::align(SECTOR_HEADER_SIZE) as usize],
+        )?;
+
+        self.active = Some((sector, generation, self.first_record(sector)));
+        Ok(())
+    }
+
+    /// Make room for `size` bytes in the active sector, opening or collecting sectors as needed.
+    fn reserve(&mut self, size: u32) -> Result<(), KvError<F::Error>> {
+        for _ in 0..=self.sectors {
+            let Some((active, generation, offset)) = self.active else {
+                self.open_sector(0, 0)?;
+                continue;
+            };
+            if offset + size <= self.sector_end(active) {
+                return Ok(());
+            }
+
+            let mut free = 0;
+            let mut next_free = None;
+            let mut oldest: Option<(u32, u32)> = None;
+            for i in 1..=self.sectors {
+                let sector = (active + i) % self.sectors;
+                match self.sector_generation(sector)? {
+                    None => {
+                        free += 1;
+                        next_free.get_or_insert(sector);
+                    }
+                    Some(g) => {
+                        if oldest.is_none_or(|(_, oldest)| g < oldest) {
+                            oldest = Some((sector, g));
+                        }
+                    }
+                }
+            }
+
+            let Some(next_free) = next_free else {
+                return Err(KvError::Full);
+            };
+            self.open_sector(next_free, generation + 1)?;
+
+            // Keep one sector erased for garbage collection.
+            if free < 2
+                && let Some((oldest, _)) = oldest
+            {
+                self.collect(oldest)?;
+            }
+        }
+
+        Err(KvError::Full)
+    }
+
+    /// Copy the live records of `sector` into the active sector, then erase it.
+    fn collect(&mut self, sector: u32) -> Result<(), KvError<F::Error>> {
+        let mut offset = self.first_record(sector);
+        while let Some(record) = self.record_at(sector, offset)? {
+            offset = self.next_offset(&record);
+            if record.len == TOMBSTONE_LEN {
+                continue;
+            }
+            match self.latest(record.key)? {
+                Some((_, latest)) if latest.offset == record.offset => {}
+                _ => continue,
+            }
+
+            let Some((active, generation, dst)) = self.active else {
+                return Err(KvError::Full);
+            };
+            let size = self.record_size(record.data_len());
+            if dst + size > self.sector_end(active) {
+                return Err(KvError::Full);
+            }
+
+            let mut buf = [0u8; BUF];
+            let mut pos = 0;
+            while pos < size {
+                let n = (size - pos).min(BUF as u32);
+                self.flash
+                    .read(record.offset + pos, &mut buf[..n as usize])?;
+                self.flash.write(dst + pos, &buf[..n as usize])?;
+                pos += n;
+            }
+            self.active = Some((active, generation, dst + size));
+        }
+
+        self.flash
+            .erase(self.sector_start(sector), self.sector_end(sector))?;
+        Ok(())
+    }
+
+    /// Write `record` with `data` at the end of the active sector.
+    fn append(&mut self, mut record: Record, data: &[u8]) -> Result<(), KvError<F::Error>> {
+        self.repair()?;
+        let size = self.record_size(data.len() as u32);
+        self.reserve(size)?;
+        let Some((active, generation, offset)) = self.active else {
+            return Err(KvError::Full);
+        };
+
+        record.offset = offset;
+        record.crc = crc16(crc16(0xFFFF, &record.header_bytes()), data);
+
+        let mut buf = [0xFFu8; BUF];
+        buf[..6].copy_from_slice(&record.header_bytes());
+        buf[6..8].copy_from_slice(&record.crc.to_le_bytes());
+
+        // Header and data are streamed through `buf`, padded with 0xFF to a write unit.
+        let mut fill = RECORD_HEADER_SIZE as usize;
+        let mut dst = offset;
+        let mut data = data;
+        loop {
+            let n = data.len().min(BUF - fill);
+            buf[fill..fill + n].copy_from_slice(&data[..n]);
+            fill += n;
+            data = &data[n..];
+
+            if data.is_empty() {
+                let len = Self::align(fill as u32) as usize;
+                buf[fill..len].fill(0xFF);
+                self.flash.write(dst, &buf[..len])?;
+                break;
+            }
+
+            self.flash.write(dst, &buf)?;
+            dst += BUF as u32;
+            fill = 0;
+        }
+
+        self.active = Some((active, generation, offset + size));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_storage::nor_flash::{ErrorType, NorFlashError, NorFlashErrorKind, ReadNorFlash};
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Eq)]
+    struct PowerLoss;
+
+    impl NorFlashError for PowerLoss {
+        fn kind(&self) -> NorFlashErrorKind {
+            NorFlashErrorKind::Other
+        }
+    }
+
+    /// Flash that loses power once `units_left` write units and erases have been done. Each unit
+    /// is programmed, and each sector erased, all at once.
+    struct Mem {
+        bytes: [u8; 1024],
+        units_left: usize,
+    }
+
+    impl Mem {
+        fn new() -> Self {
+            Self {
+                bytes: [0xFF; 1024],
+                units_left: usize::MAX,
+            }
+        }
+
+        fn spend(&mut self) -> Result<(), PowerLoss> {
+            self.units_left = self.units_left.checked_sub(1).ok_or(PowerLoss)?;
+            Ok(())
+        }
+    }
+
+    impl ErrorType for Mem {
+        type Error = PowerLoss;
+    }
+
+    impl ReadNorFlash for Mem {
+        const READ_SIZE: usize = 1;
+
+        fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), PowerLoss> {
+            let offset = offset as usize;
+            bytes.copy_from_slice(&self.bytes[offset..offset + bytes.len()]);
+            Ok(())
+        }
+
+        fn capacity(&self) -> usize {
+            self.bytes.len()
+        }
+    }
+
+    impl NorFlash for Mem {
+        const WRITE_SIZE: usize = 4;
+        const ERASE_SIZE: usize = 256;
+
+        fn erase(&mut self, from: u32, to: u32) -> Result<(), PowerLoss> {
+            for sector in (from..to).step_by(Self::ERASE_SIZE) {
+                self.spend()?;
+                let sector = sector as usize;
+                self.bytes[sector..sector + Self::ERASE_SIZE].fill(0xFF);
+            }
+            Ok(())
+        }
+
+        fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), PowerLoss> {
+            assert!(
+                offset.is_multiple_of(Self::WRITE_SIZE as u32)
+                    && bytes.len().is_multiple_of(Self::WRITE_SIZE)
+            );
+            for (i, unit) in bytes.chunks(Self::WRITE_SIZE).enumerate() {
+                self.spend()?;
+                let offset = offset as usize + i * Self::WRITE_SIZE;
+                for (dst, src) in self.bytes[offset..offset + Self::WRITE_SIZE]
+                    .iter_mut()
+                    .zip(unit)
+                {
+                    *dst &= src;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    type Store<'a> = KvStore<'a, Mem, 32>;
+
+    fn value(key: u32, round: u32) -> [u8; 20] {
+        [(key * 16 + round) as u8; 20]
+    }
+
+    fn get(mem: &mut Mem, range: Range<u32>, key: u32) -> Option<[u8; 20]> {
+        let mut kv = Store::new(mem, range).unwrap();
+        let mut buf = [0; 20];
+        kv.get(key, &mut buf).unwrap().map(|len| {
+            assert_eq!(len, 20);
+            buf
+        })
+    }
+
+    /// Overwrite four keys until the store has been collected several times over.
+    fn gc(range: Range<u32>) {
+        let mut mem = Mem::new();
+        let mut kv = Store::new(&mut mem, range.clone()).unwrap();
+        for round in 0..12 {
+            for key in 0..4 {
+                kv.insert(key, &value(key, round)).unwrap();
+            }
+        }
+
+        for key in 0..4 {
+            assert_eq!(get(&mut mem, range.clone(), key), Some(value(key, 11)));
+        }
+    }
+
+    #[test]
+    fn gc_two_sectors() {
+        gc(0..512);
+    }
+
+    #[test]
+    fn gc_three_sectors() {
+        gc(0..768);
+    }
+
+    #[test]
+    fn tombstones_survive_collection() {
+        let mut mem = Mem::new();
+        let mut kv = Store::new(&mut mem, 0..512).unwrap();
+        kv.insert(0, &value(0, 0)).unwrap();
+        kv.insert(1, &value(1, 0)).unwrap();
+        kv.remove(0).unwrap();
+        for round in 0..12 {
+            kv.insert(1, &value(1, round)).unwrap();
+        }
+
+        assert_eq!(get(&mut mem, 0..512, 0), None);
+        assert_eq!(get(&mut mem, 0..512, 1), Some(value(1, 11)));
+    }
+
+    #[test]
+    fn repair_resumes_interrupted_collection() {
+        // Fill the first of two sectors, so the next write opens the second and collects into it.
+        let mut full = Mem::new();
+        let mut kv = Store::new(&mut full, 0..512).unwrap();
+        for key in 0..8 {
+            kv.insert(key % 3, &value(key % 3, key)).unwrap();
+        }
+        assert_eq!(kv.active.map(|(sector, _, _)| sector), Some(0));
+
+        let mut cut = 0;
+        loop {
+            let mut mem = Mem {
+                units_left: cut,
+                ..Mem::new()
+            };
+            mem.bytes = full.bytes;
+            let done = Store::new(&mut mem, 0..512)
+                .unwrap()
+                .insert(3, &value(3, 0))
+                .is_ok();
+
+            mem.units_left = usize::MAX;
+            Store::new(&mut mem, 0..512).unwrap().repair().unwrap();
+            for key in 0..3 {
+                let round = [6, 7, 5][key as usize];
+                assert_eq!(
+                    get(&mut mem, 0..512, key),
+                    Some(value(key, round)),
+                    "cut at {cut}"
+                );
+            }
+            assert!(!done || get(&mut mem, 0..512, 3) == Some(value(3, 0)));
+
+            let mut kv = Store::new(&mut mem, 0..512).unwrap();
+            for round in 1..8 {
+                kv.insert(3, &value(3, round)).unwrap();
+            }
+            assert_eq!(get(&mut mem, 0..512, 3), Some(value(3, 7)));
+
+            if done {
+                break;
+            }
+            cut += 1;
+        }
+    }
+
+    #[test]
+    fn torn_header_is_not_programmed_over() {
+        let mut mem = Mem::new();
+        Store::new(&mut mem, 0..512)
+            .unwrap()
+            .insert(1, &value(1, 0))
+            .unwrap();
+
+        // Only the key of the header makes it, leaving a length that runs past the sector.
+        mem.units_left = 1;
+        assert_eq!(
+            Store::new(&mut mem, 0..512)
+                .unwrap()
+                .insert(2, &value(2, 0)),
+            Err(KvError::Flash(PowerLoss))
+        );
+        mem.units_left = usize::MAX;
+
+        Store::new(&mut mem, 0..512)
+            .unwrap()
+            .insert(3, &value(3, 0))
+            .unwrap();
+        assert_eq!(get(&mut mem, 0..512, 1), Some(value(1, 0)));
+        assert_eq!(get(&mut mem, 0..512, 2), None);
+        assert_eq!(get(&mut mem, 0..512, 3), Some(value(3, 0)));
+    }
+}
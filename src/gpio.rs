@@ -1,4 +1,7 @@
-use core::convert::Infallible;
+use core::{
+    convert::Infallible,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
 use crate::{pac, peripherals};
 use embassy_hal_internal::{Peri, PeripheralType, impl_peripheral};
@@ -43,6 +46,19 @@ pub enum Level {
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Unsupported;
 
+/// Whether PIO0_10 (SWCLK) and PIO0_15 (SWDIO) are kept on their debug function.
+static SWD_PINS_RESERVED: AtomicBool = AtomicBool::new(false);
+
+/// Reserve or release the SWD pins, restoring their debug function when reserving them.
+pub(crate) fn reserve_swd_pins(reserve: bool) {
+    SWD_PINS_RESERVED.store(reserve, Ordering::Relaxed);
+
+    if reserve {
+        IOCON.port0().p(10).modify(|r| r.set_func(0));
+        IOCON.port0().p(15).modify(|r| r.set_func(0));
+    }
+}
+
 pub(crate) trait SealedPin: Sized {
     fn port_pin(&self) -> u8;
 
@@ -187,6 +203,11 @@ pub struct Flex<'d> {
 
 impl<'d> Flex<'d> {
     pub fn new(pin: Peri<'d, impl Pin + 'd>) -> Self {
+        assert!(
+            !(matches!(pin.port_pin(), 10 | 15) && SWD_PINS_RESERVED.load(Ordering::Relaxed)),
+            "SWD pins are reserved, see `Config::reserve_swd_pins`"
+        );
+
         pin.iocon().modify(|r: &mut lpc11uxx2::iocon::regs::Pio| {
             r.set_func(pin.pio_func());
 
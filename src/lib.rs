@@ -1,3 +1,17 @@
+//! Hardware abstraction layer for the NXP LPC11U3x microcontrollers.
+//!
+//! # Linker script
+//!
+//! The ROM's IAP routines, used for flash and EEPROM access, take the top 32 bytes of RAM as
+//! scratch space. Programs using them should include the `iap.x` fragment from `memory.x`, after
+//! the `MEMORY` block, to move the stack below that area:
+//!
+//! ```text
+//! INCLUDE iap.x
+//! ```
+//!
+//! Linking then fails if the stack still overlaps the scratch area.
+
 #![no_std]
 #![allow(clippy::missing_safety_doc)]
 #![cfg_attr(feature = "intrinsics", feature(abi_custom))]
@@ -19,7 +33,16 @@ pub mod usart;
 pub mod usb;
 pub mod watchdog;
 
-embassy_hal_internal::interrupt_mod! {
+macro_rules! hal_interrupts {
+    ($($irq:ident),* $(,)?) => {
+        embassy_hal_internal::interrupt_mod!($($irq),*);
+
+        /// Every interrupt managed by the HAL.
+        const INTERRUPTS: &[interrupt::Interrupt] = &[$(interrupt::Interrupt::$irq),*];
+    };
+}
+
+hal_interrupts! {
     PIN_INT0,
     PIN_INT1,
     PIN_INT2,
@@ -150,26 +173,164 @@ embassy_hal_internal::peripherals! {
 }
 
 pub mod config {
+    use crate::clocks::ClockConfig;
+    use crate::interrupt::{Interrupt, Priority};
+
+    /// Priority given to every interrupt unless overridden with [`Config::interrupt_priority`].
+    pub const DEFAULT_INTERRUPT_PRIORITY: Priority = Priority::P0;
+
+    /// Brown-out detector reset level.
+    ///
+    /// The first voltage is the assertion level (falling supply), the second the de-assertion
+    /// level (rising supply).
+    #[repr(u8)]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub enum BrownOutLevel {
+        /// 1.46V / 1.63V
+        Level0 = 0,
+        /// 2.06V / 2.15V
+        Level1 = 1,
+        /// 2.35V / 2.43V
+        Level2 = 2,
+        /// 2.63V / 2.71V
+        Level3 = 3,
+    }
+
     #[non_exhaustive]
     pub struct Config {
-
+        /// Clock tree applied by [`init`][crate::init].
+        pub clocks: ClockConfig,
+        /// Priority of each interrupt, indexed by interrupt number.
+        pub interrupt_priorities: [Priority; 32],
+        /// Reset the chip when the supply drops below this level. The brown-out detector is
+        /// powered down when `None`.
+        pub brown_out: Option<BrownOutLevel>,
+        /// Keep PIO0_10 (SWCLK) and PIO0_15 (SWDIO) on their debug function.
+        ///
+        /// While reserved, using either pin as GPIO panics.
+        pub reserve_swd_pins: bool,
+        /// Read the part ID and panic unless it matches the part feature the crate was built
+        /// for.
+        pub verify_part: bool,
     }
 
     impl Default for Config {
         fn default() -> Self {
-            todo!()
+            Self::new()
         }
     }
 
     impl Config {
-        pub fn new() -> Self {
-            todo!()
+        pub const fn new() -> Self {
+            Self {
+                clocks: ClockConfig::irc_12mhz(),
+                interrupt_priorities: [DEFAULT_INTERRUPT_PRIORITY; 32],
+                brown_out: None,
+                reserve_swd_pins: true,
+                verify_part: false,
+            }
+        }
+
+        /// Set the clock tree.
+        pub const fn clocks(mut self, clocks: ClockConfig) -> Self {
+            self.clocks = clocks;
+            self
+        }
+
+        /// Set the priority of a single interrupt.
+        pub const fn interrupt_priority(mut self, irq: Interrupt, priority: Priority) -> Self {
+            self.interrupt_priorities[irq as usize] = priority;
+            self
+        }
+
+        /// Set the priority of every interrupt.
+        pub const fn default_interrupt_priority(mut self, priority: Priority) -> Self {
+            self.interrupt_priorities = [priority; 32];
+            self
+        }
+
+        /// Set the brown-out reset level, or disable the brown-out detector with `None`.
+        pub const fn brown_out(mut self, level: Option<BrownOutLevel>) -> Self {
+            self.brown_out = level;
+            self
+        }
+
+        /// Choose whether the SWD pins stay reserved for debugging.
+        pub const fn reserve_swd_pins(mut self, reserve: bool) -> Self {
+            self.reserve_swd_pins = reserve;
+            self
+        }
+
+        /// Choose whether [`init`][crate::init] checks the part ID against the part feature.
+        pub const fn verify_part(mut self, verify: bool) -> Self {
+            self.verify_part = verify;
+            self
         }
     }
 }
 
-pub fn init(_config: config::Config) -> Peripherals {
-    let _peripherals = Peripherals::take();
+/// Initialize the HAL.
+///
+/// This applies the clock configuration, configures the brown-out detector, enables the AHB
+/// clocks needed by the GPIO and pin interrupt drivers, takes the peripherals out of reset and
+/// sets the priority of every HAL interrupt.
+///
+/// # Panics
+/// Panics if called more than once, or if [`Config::verify_part`][config::Config::verify_part]
+/// is set and the chip is not the part the crate was built for.
+pub fn init(config: config::Config) -> Peripherals {
+    let peripherals = Peripherals::take();
+
+    if config.verify_part {
+        let part_id = rom::read_part_id();
+        match part_id.variant() {
+            Some(variant) if variant.matches_target() => {}
+            Some(variant) => panic!("running on {:?}, built for another part", variant),
+            None => panic!("unknown part ID {:?}", part_id),
+        }
+    }
+
+    critical_section::with(|_| {
+        unwrap!(unsafe { clocks::init(config.clocks) });
+
+        match config.brown_out {
+            Some(level) => {
+                pac::SYSCON.pdruncfg().modify(|w| w.set_bod_pd(false));
+                pac::SYSCON.bodctrl().write(|w| {
+                    w.set_bodrstlev(level as u8);
+                    w.set_bodrstena(true);
+                });
+            }
+            None => {
+                pac::SYSCON.bodctrl().write(|w| w.set_bodrstena(false));
+                pac::SYSCON.pdruncfg().modify(|w| w.set_bod_pd(true));
+            }
+        }
+
+        pac::SYSCON.sysahbclkctrl().modify(|w| {
+            w.set_gpio(true);
+            w.set_iocon(true);
+            w.set_pint(true);
+            w.set_group0int(true);
+            w.set_group1int(true);
+        });
+
+        pac::SYSCON.presetctrl().modify(|w| {
+            w.set_ssp0_rst_n(true);
+            w.set_i2c_rst_n(true);
+            w.set_ssp1_rst_n(true);
+        });
+
+        gpio::reserve_swd_pins(config.reserve_swd_pins);
+
+        for &irq in INTERRUPTS {
+            use interrupt::InterruptExt;
+
+            irq.unpend();
+            irq.set_priority(config.interrupt_priorities[irq as usize]);
+        }
+    });
 
-    todo!()
+    peripherals
 }
@@ -1,3 +1,5 @@
+use core::convert::Infallible;
+
 #[repr(C)]
 pub struct _DivReturn<T> {
     pub quot: T,
@@ -65,10 +67,133 @@ impl RomDrivers {
     }
 }
 
+pub mod power {
+    //! Power profiles from the ROM power driver.
+
+    use super::RomDrivers;
+
+    /// Power profile selected by [`set_power`].
+    #[repr(u32)]
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub enum PowerMode {
+        /// Reset state of the internal regulator.
+        #[default]
+        Default = 0,
+        /// Optimized for CPU performance.
+        CpuPerformance = 1,
+        /// Optimized for the best balance of current consumption and CPU performance.
+        Efficiency = 2,
+        /// Optimized for the lowest current consumption.
+        LowCurrent = 3,
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub enum SetPowerError {
+        /// The frequency is not between 1 and 50MHz.
+        InvalidFrequency,
+        /// The mode is not one of [`PowerMode`].
+        InvalidMode,
+        /// The ROM returned an undocumented status code.
+        Unknown(u32),
+    }
+
+    /// How [`set_pll`] may deviate from the requested frequency.
+    #[repr(u32)]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub enum SetPllMode {
+        /// The system clock must equal the requested frequency.
+        Equal = 0,
+        /// The system clock must be lower than or equal to the requested frequency.
+        Lower = 1,
+        /// The system clock must be greater than or equal to the requested frequency.
+        Greater = 2,
+        /// The system clock must be within 1MHz of the requested frequency.
+        Approximate = 3,
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub enum SetPllError {
+        /// The input or requested frequency is out of range.
+        InvalidFrequency,
+        /// The mode is not one of [`SetPllMode`].
+        InvalidMode,
+        /// No PLL setting satisfies the requested frequency and mode.
+        FrequencyNotFound,
+        /// The PLL did not lock within the timeout.
+        NotLocked,
+        /// The ROM returned an undocumented status code.
+        Unknown(u32),
+    }
+
+    /// Let the ROM configure the system PLL to produce a system clock of `target_khz` from the
+    /// `input_khz` PLL input selected in SYSPLLCLKSEL.
+    ///
+    /// On success, the main clock is switched to the PLL output (or its input, if no
+    /// multiplication is needed), SYSAHBCLKDIV is updated and the resulting system clock in kHz
+    /// is returned. `lock_timeout` bounds the number of polls of the PLL lock bit.
+    ///
+    /// # Safety
+    /// This switches the system clock without updating flash wait states, the power profile or
+    /// the frequencies reported by [`clocks`][crate::clocks]. Prefer
+    /// [`PllStrategy::Rom`][crate::clocks::PllStrategy::Rom].
+    #[inline]
+    pub unsafe fn set_pll(
+        input_khz: u32,
+        target_khz: u32,
+        mode: SetPllMode,
+        lock_timeout: u32,
+    ) -> Result<u32, SetPllError> {
+        let cmd = [input_khz, target_khz, mode as u32, lock_timeout];
+        let mut resp = [0u32; 2];
+
+        unsafe { (RomDrivers::power().set_pll)(cmd.as_ptr(), resp.as_mut_ptr()) };
+
+        match resp[0] {
+            0 => Ok(resp[1]),
+            1 => Err(SetPllError::InvalidFrequency),
+            2 => Err(SetPllError::InvalidMode),
+            3 => Err(SetPllError::FrequencyNotFound),
+            4 => Err(SetPllError::NotLocked),
+            code => Err(SetPllError::Unknown(code)),
+        }
+    }
+
+    /// Configure the internal regulator for running at `cclk_mhz` in the given `mode`.
+    ///
+    /// Must be called whenever the system clock changes; [`clocks`][crate::clocks] does this
+    /// automatically.
+    #[inline]
+    pub fn set_power(mode: PowerMode, cclk_mhz: u32) -> Result<(), SetPowerError> {
+        let cmd = [cclk_mhz, mode as u32, cclk_mhz];
+        let mut resp = [0u32; 1];
+
+        unsafe { (RomDrivers::power().set_power)(cmd.as_ptr(), resp.as_mut_ptr()) };
+
+        match resp[0] {
+            0 => Ok(()),
+            1 => Err(SetPowerError::InvalidFrequency),
+            2 => Err(SetPowerError::InvalidMode),
+            code => Err(SetPowerError::Unknown(code)),
+        }
+    }
+}
+
 #[inline(always)]
+/// Call the IAP entry point.
+///
+/// The IAP routines run from ROM and only return once the flash is readable again, so they can be
+/// called from flash. Interrupts are left enabled, so the EEPROM commands don't hold off interrupt
+/// handlers.
+///
 /// # Safety
-/// This can overwrite running code and cause undefined behavior.
-/// Always run code that can modify flash from RAM.
+/// This can overwrite running code and cause undefined behavior. The vector table and interrupt
+/// handlers stay in flash, so commands that program or erase flash must be called with interrupts
+/// masked. The top 32 bytes of RAM are used as scratch space; see the [crate docs][crate] for
+/// keeping the stack out of them.
 pub unsafe fn iap_entry(command_param: &[u32], status_result: &mut [u32]) {
     unsafe {
         core::mem::transmute::<*const (), _CmdResp>(IAP)(
@@ -78,6 +203,24 @@ pub unsafe fn iap_entry(command_param: &[u32], status_result: &mut [u32]) {
     };
 }
 
+/// Core clock in kHz, as required by the IAP flash and EEPROM commands.
+#[inline]
+pub fn cclk_khz() -> u32 {
+    crate::clocks::mainclk_hz() / 1_000
+}
+
+/// Read `dst.len()` bytes of EEPROM starting at `offset`.
+#[inline]
+pub fn eeprom_read(offset: u32, dst: &mut [u8]) -> IapResult<()> {
+    unsafe { read_eeprom(offset, dst.as_mut_ptr(), dst.len() as u32, cclk_khz()) }
+}
+
+/// Write `src` to EEPROM starting at `offset`.
+#[inline]
+pub fn eeprom_write(offset: u32, src: &[u8]) -> IapResult<()> {
+    unsafe { write_eeprom(offset, src.as_ptr(), src.len() as u32, cclk_khz()) }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(C, u32, align(4))]
@@ -143,22 +286,117 @@ impl From<IapCommand> for [u32; 5] {
     }
 }
 
+/// Status of an IAP command, decoded from its response words.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
-#[repr(C, u32, align(4))]
 pub enum IapResult<T: Sized> {
-    Success(T) = 0,
-    InvalidCommand = 1,
-    SrcAddrError = 2,
-    DstAddrError = 3,
-    SrcAddrNotMapped = 4,
-    DstAddrNotMapped = 5,
-    CountError = 6,
-    InvalidSector = 7,
-    SectorNotBlank { first_offset: usize, contents: u32 } = 8,
-    SectorNotPreparedForWriteOperation = 9,
-    CompareError { first_offset: usize } = 10,
-    Busy = 11,
+    Success(T),
+    InvalidCommand,
+    SrcAddrError,
+    DstAddrError,
+    SrcAddrNotMapped,
+    DstAddrNotMapped,
+    CountError,
+    InvalidSector,
+    SectorNotBlank { first_offset: usize, contents: u32 },
+    SectorNotPreparedForWriteOperation,
+    CompareError { first_offset: usize },
+    Busy,
+    /// A status code not documented in the user manual.
+    Unknown(u32),
+}
+
+/// A failed [`IapResult`].
+pub type IapError = IapResult<Infallible>;
+
+impl<T: IapResponse> IapResult<T> {
+    /// Decode the status code and result words written by the ROM.
+    #[inline]
+    pub fn decode(resp: &[u32; 5]) -> Self {
+        match resp[0] {
+            0 => IapResult::Success(T::from_words(&[resp[1], resp[2], resp[3], resp[4]])),
+            1 => IapResult::InvalidCommand,
+            2 => IapResult::SrcAddrError,
+            3 => IapResult::DstAddrError,
+            4 => IapResult::SrcAddrNotMapped,
+            5 => IapResult::DstAddrNotMapped,
+            6 => IapResult::CountError,
+            7 => IapResult::InvalidSector,
+            8 => IapResult::SectorNotBlank {
+                first_offset: resp[1] as usize,
+                contents: resp[2],
+            },
+            9 => IapResult::SectorNotPreparedForWriteOperation,
+            10 => IapResult::CompareError {
+                first_offset: resp[1] as usize,
+            },
+            11 => IapResult::Busy,
+            code => IapResult::Unknown(code),
+        }
+    }
+}
+
+impl<T> IapResult<T> {
+    /// Split into the returned value and an [`IapError`].
+    #[inline]
+    pub fn into_result(self) -> Result<T, IapError> {
+        match self {
+            IapResult::Success(t) => Ok(t),
+            IapResult::InvalidCommand => Err(IapResult::InvalidCommand),
+            IapResult::SrcAddrError => Err(IapResult::SrcAddrError),
+            IapResult::DstAddrError => Err(IapResult::DstAddrError),
+            IapResult::SrcAddrNotMapped => Err(IapResult::SrcAddrNotMapped),
+            IapResult::DstAddrNotMapped => Err(IapResult::DstAddrNotMapped),
+            IapResult::CountError => Err(IapResult::CountError),
+            IapResult::InvalidSector => Err(IapResult::InvalidSector),
+            IapResult::SectorNotBlank {
+                first_offset,
+                contents,
+            } => Err(IapResult::SectorNotBlank {
+                first_offset,
+                contents,
+            }),
+            IapResult::SectorNotPreparedForWriteOperation => {
+                Err(IapResult::SectorNotPreparedForWriteOperation)
+            }
+            IapResult::CompareError { first_offset } => {
+                Err(IapResult::CompareError { first_offset })
+            }
+            IapResult::Busy => Err(IapResult::Busy),
+            IapResult::Unknown(code) => Err(IapResult::Unknown(code)),
+        }
+    }
+}
+
+/// Value returned by an IAP command in the response words following the status code.
+pub trait IapResponse: Sized {
+    fn from_words(words: &[u32; 4]) -> Self;
+}
+
+impl IapResponse for () {
+    #[inline]
+    fn from_words(_words: &[u32; 4]) -> Self {}
+}
+
+impl IapResponse for PartId {
+    #[inline]
+    fn from_words(words: &[u32; 4]) -> Self {
+        PartId(words[0])
+    }
+}
+
+impl IapResponse for BootCodeVersion {
+    #[inline]
+    fn from_words(words: &[u32; 4]) -> Self {
+        BootCodeVersion(words[0])
+    }
+}
+
+impl IapResponse for Uid {
+    #[inline]
+    fn from_words(words: &[u32; 4]) -> Self {
+        Uid(*words)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -166,6 +404,155 @@ pub enum IapResult<T: Sized> {
 #[repr(C, align(4))]
 pub struct PartId(pub u32);
 
+impl PartId {
+    /// The chip variant this part ID belongs to, if known.
+    pub const fn variant(&self) -> Option<ChipVariant> {
+        Some(match self.0 {
+            0x095C_802B | 0x295C_802B => ChipVariant::Lpc11u12,
+            0x097A_802B | 0x297A_802B => ChipVariant::Lpc11u13,
+            0x0998_802B | 0x2998_802B => ChipVariant::Lpc11u14,
+            0x2972_402B => ChipVariant::Lpc11u22,
+            0x2988_402B => ChipVariant::Lpc11u23,
+            0x2980_002B => ChipVariant::Lpc11u24,
+            0x0003_D440 => ChipVariant::Lpc11u34_311,
+            0x0001_CC40 => ChipVariant::Lpc11u34_421,
+            0x0001_BC40 => ChipVariant::Lpc11u35_401,
+            0x0000_BC40 => ChipVariant::Lpc11u35_501,
+            0x0001_9C40 => ChipVariant::Lpc11u36_401,
+            0x0001_7C40 => ChipVariant::Lpc11u37_401,
+            0x0000_7C44 => ChipVariant::Lpc11u37h_401,
+            0x0000_7C40 => ChipVariant::Lpc11u37_501,
+            _ => return None,
+        })
+    }
+}
+
+/// LPC11Uxx variant, as identified by its [`PartId`].
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ChipVariant {
+    Lpc11u12,
+    Lpc11u13,
+    Lpc11u14,
+    Lpc11u22,
+    Lpc11u23,
+    Lpc11u24,
+    Lpc11u34_311,
+    Lpc11u34_421,
+    Lpc11u35_401,
+    Lpc11u35_501,
+    Lpc11u36_401,
+    Lpc11u37_401,
+    Lpc11u37h_401,
+    Lpc11u37_501,
+}
+
+impl ChipVariant {
+    /// On-chip flash size in bytes.
+    pub const fn flash_size(&self) -> usize {
+        match self {
+            ChipVariant::Lpc11u12 | ChipVariant::Lpc11u22 => 16 * 1024,
+            ChipVariant::Lpc11u13 | ChipVariant::Lpc11u23 => 24 * 1024,
+            ChipVariant::Lpc11u14 | ChipVariant::Lpc11u24 => 32 * 1024,
+            ChipVariant::Lpc11u34_311 => 40 * 1024,
+            ChipVariant::Lpc11u34_421 => 48 * 1024,
+            ChipVariant::Lpc11u35_401 | ChipVariant::Lpc11u35_501 => 64 * 1024,
+            ChipVariant::Lpc11u36_401 => 96 * 1024,
+            ChipVariant::Lpc11u37_401 | ChipVariant::Lpc11u37h_401 | ChipVariant::Lpc11u37_501 => {
+                128 * 1024
+            }
+        }
+    }
+
+    /// Main SRAM (SRAM0) size in bytes.
+    pub const fn sram0_size(&self) -> usize {
+        match self {
+            ChipVariant::Lpc11u12
+            | ChipVariant::Lpc11u13
+            | ChipVariant::Lpc11u14
+            | ChipVariant::Lpc11u22
+            | ChipVariant::Lpc11u23 => 4 * 1024,
+            ChipVariant::Lpc11u24 => 6 * 1024,
+            _ => 8 * 1024,
+        }
+    }
+
+    /// SRAM1 size in bytes.
+    pub const fn sram1_size(&self) -> usize {
+        match self {
+            ChipVariant::Lpc11u35_401
+            | ChipVariant::Lpc11u35_501
+            | ChipVariant::Lpc11u36_401
+            | ChipVariant::Lpc11u37_401
+            | ChipVariant::Lpc11u37h_401
+            | ChipVariant::Lpc11u37_501 => 2 * 1024,
+            _ => 0,
+        }
+    }
+
+    /// USB SRAM size in bytes.
+    pub const fn usb_ram_size(&self) -> usize {
+        match self {
+            ChipVariant::Lpc11u34_311 => 0,
+            _ => 2 * 1024,
+        }
+    }
+
+    /// EEPROM size in bytes, including the reserved top 64 bytes of 4 kB parts.
+    pub const fn eeprom_size(&self) -> usize {
+        match self {
+            ChipVariant::Lpc11u12 | ChipVariant::Lpc11u13 | ChipVariant::Lpc11u14 => 0,
+            ChipVariant::Lpc11u22 | ChipVariant::Lpc11u23 => 1024,
+            ChipVariant::Lpc11u24 => 2 * 1024,
+            _ => 4 * 1024,
+        }
+    }
+
+    /// Whether the part implements the IAP page erase command.
+    pub const fn has_page_erase(&self) -> bool {
+        matches!(
+            self,
+            ChipVariant::Lpc11u34_311
+                | ChipVariant::Lpc11u34_421
+                | ChipVariant::Lpc11u35_401
+                | ChipVariant::Lpc11u35_501
+                | ChipVariant::Lpc11u36_401
+                | ChipVariant::Lpc11u37_401
+                | ChipVariant::Lpc11u37h_401
+                | ChipVariant::Lpc11u37_501
+        )
+    }
+
+    /// Whether this variant is covered by the part feature the crate was built with.
+    ///
+    /// Always `true` when no part feature is enabled.
+    pub const fn matches_target(&self) -> bool {
+        #[cfg(feature = "lpc11u34")]
+        return matches!(self, ChipVariant::Lpc11u34_311 | ChipVariant::Lpc11u34_421);
+
+        #[cfg(feature = "lpc11u35")]
+        return matches!(self, ChipVariant::Lpc11u35_401 | ChipVariant::Lpc11u35_501);
+
+        #[cfg(feature = "lpc11u36")]
+        return matches!(self, ChipVariant::Lpc11u36_401);
+
+        #[cfg(feature = "lpc11u37")]
+        return matches!(
+            self,
+            ChipVariant::Lpc11u37_401 | ChipVariant::Lpc11u37h_401 | ChipVariant::Lpc11u37_501
+        );
+
+        #[cfg(not(any(
+            feature = "lpc11u34",
+            feature = "lpc11u35",
+            feature = "lpc11u36",
+            feature = "lpc11u37"
+        )))]
+        return true;
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(C, align(4))]
@@ -193,8 +580,8 @@ macro_rules! impl_iap_functions {
 
             unsafe {
                 iap_entry(&cmd, &mut []);
-                core::hint::unreachable_unchecked();
             }
+            panic!("IAP command returned")
         }
         impl_iap_functions!($($tt)*);
     };
@@ -210,13 +597,10 @@ macro_rules! impl_iap_functions {
             let cmd: [u32; 5] = IapCommand::$variant {
                 $($arg: $arg as _),*
             }.into();
-            let mut resp = [0u32; size_of::<IapResult<$ty>>() / 4];
+            let mut resp = [0u32; 5];
 
-            unsafe {
-                iap_entry(&cmd, &mut resp);
-                let ret = core::mem::transmute::<[u32; size_of::<IapResult<$ty>>() / 4], IapResult<$ty>>(resp);
-                ret
-            }
+            unsafe { iap_entry(&cmd, &mut resp) };
+            IapResult::decode(&resp)
         }
 
         impl_iap_functions!($($tt)*);
@@ -233,13 +617,10 @@ macro_rules! impl_iap_functions {
             let cmd: [u32; 5] = IapCommand::$variant {
                 $($arg: $arg as _),*
             }.into();
-            let mut resp = [0u32; size_of::<IapResult<$ty>>() / 4];
+            let mut resp = [0u32; 5];
 
-            unsafe {
-                iap_entry(&cmd, &mut resp);
-                let ret = core::mem::transmute::<[u32; size_of::<IapResult<$ty>>() / 4], IapResult<$ty>>(resp);
-                ret
-            }
+            unsafe { iap_entry(&cmd, &mut resp) };
+            IapResult::decode(&resp)
         }
         impl_iap_functions!($($tt)*);
 
@@ -247,50 +628,23 @@ macro_rules! impl_iap_functions {
 
     (
         $(#[$meta:meta])*
-        $variant:ident: fn $name:ident ($($arg:ident: $argty:ty),*$(,)?) -> $ty:ty,
+        $variant:ident: fn $name:ident ($($arg:ident: $argty:ty),*$(,)?) -> Result<$ty:ty, IapError>,
         $($tt:tt)*
     ) => {
         $(#[$meta])*
         #[inline(always)]
-        pub fn $name($($arg: $argty),*) -> $ty {
+        pub fn $name($($arg: $argty),*) -> Result<$ty, IapError> {
             let cmd: [u32; 5] = IapCommand::$variant {
                 $($arg: $arg as _),*
             }.into();
-            let mut resp = [0u32; size_of::<IapResult<$ty>>() / 4];
+            let mut resp = [0u32; 5];
 
-            unsafe {
-                iap_entry(&cmd, &mut resp);
-                let IapResult::Success(ret) = core::mem::transmute::<[u32; size_of::<IapResult<$ty>>() / 4], IapResult<$ty>>(resp)
-                    else { core::hint::unreachable_unchecked() };
-                ret
-            }
+            unsafe { iap_entry(&cmd, &mut resp) };
+            IapResult::decode(&resp).into_result()
         }
         impl_iap_functions!($($tt)*);
 
     };
-
-    (
-        $(#[$meta:meta])*
-        $variant:ident: unsafe fn $name:ident ($($arg:ident: $argty:ty),*$(,)?) -> $ty:ty,
-        $($tt:tt)*
-    ) => {
-        $(#[$meta])*
-        #[inline(always)]
-        pub unsafe fn $name($($arg: $argty),*) -> $ty {
-            let cmd: [u32; 5] = IapCommand::$variant {
-                $($arg: $arg as _),*
-            }.into();
-            let mut resp = [0u32; size_of::<IapResult<$ty>>() / 4];
-
-            unsafe {
-                iap_entry(&cmd, &mut resp);
-                let IapResult::Success(ret) = core::mem::transmute::<[u32; size_of::<IapResult<$ty>>() / 4], IapResult<$ty>>(resp)
-                    else { core::hint::unreachable_unchecked() };
-                ret
-            }
-        }
-        impl_iap_functions!($($tt)*);
-    };
 }
 
 impl_iap_functions! {
@@ -310,6 +664,8 @@ impl_iap_functions! {
     /// affected sectors are automatically protected again once the copy command is
     /// successfully executed. The boot sector can not be written by this command. Also
     /// see Section 20.6 for the number of bytes that can be written.
+    /// 
+    /// Must be called with interrupts masked, since the vector table is in flash.
     CopyRamToFlash: unsafe fn copy_ram_to_flash(dst: *mut (), src: *const (), nbytes: u32, cclk_khz: u32) -> IapResult<()>,
 
     /// Erase Sector(s)
@@ -317,6 +673,8 @@ impl_iap_functions! {
     /// This command is used to erase a sector or multiple sectors of on-chip flash
     /// memory. The boot sector can not be erased by this command. To erase a single
     /// sector use the same "Start" and "End" sector numbers.
+    /// 
+    /// Must be called with interrupts masked, since the vector table is in flash.
     EraseSectors: unsafe fn erase_sectors(first: u32, last: u32, cclk_khz: u32) -> IapResult<()>,
 
     /// Blank check sector(s)
@@ -329,12 +687,12 @@ impl_iap_functions! {
     /// Read Part Identification number
     /// 
     /// This command is used to read the part identification number.
-    ReadPartId: fn read_part_id() -> PartId,
+    ReadPartId: fn try_read_part_id() -> Result<PartId, IapError>,
 
     /// Read Boot code version number
     /// 
     /// This command is used to read the boot code version number.
-    ReadBootCodeVersion: fn read_boot_code_version() -> BootCodeVersion,
+    ReadBootCodeVersion: fn try_read_boot_code_version() -> Result<BootCodeVersion, IapError>,
 
     /// Compare <address1> <address2> <no of bytes>
     /// This command is used to compare the memory contents at two locations.
@@ -356,7 +714,7 @@ impl_iap_functions! {
     /// Read UID
     /// 
     /// This command is used to read the unique ID.
-    ReadUid: fn read_uid() -> Uid,
+    ReadUid: fn try_read_uid() -> Result<Uid, IapError>,
 
     #[cfg(any(
         feature = "lpc11u34",
@@ -369,6 +727,8 @@ impl_iap_functions! {
     /// This command is used to erase a page or multiple pages of on-chip flash memory.
     /// To erase a single page use the same "start" and "end" page numbers. See
     /// Table 343 for list of parts that implement this command.
+    /// 
+    /// Must be called with interrupts masked, since the vector table is in flash.
     ErasePage: unsafe fn erase_page(first_page: u32, last_page: u32, cclk_khz: u32) -> IapResult<()>,
 
     /// Write EEPROM
@@ -384,3 +744,27 @@ impl_iap_functions! {
     /// Data is copied from the EEPROM address to the RAM address.
     ReadEeprom: unsafe fn read_eeprom(eeprom_src: u32, dst: *mut u8, nbytes: u32, cclk_khz: u32) -> IapResult<()>,
 }
+
+/// Read Part Identification number
+///
+/// Panics if the ROM reports an error; see [`try_read_part_id`].
+#[inline(always)]
+pub fn read_part_id() -> PartId {
+    unwrap!(try_read_part_id())
+}
+
+/// Read Boot code version number
+///
+/// Panics if the ROM reports an error; see [`try_read_boot_code_version`].
+#[inline(always)]
+pub fn read_boot_code_version() -> BootCodeVersion {
+    unwrap!(try_read_boot_code_version())
+}
+
+/// Read UID
+///
+/// Panics if the ROM reports an error; see [`try_read_uid`].
+#[inline(always)]
+pub fn read_uid() -> Uid {
+    unwrap!(try_read_uid())
+}
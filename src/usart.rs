@@ -0,0 +1,64 @@
+/// USART baud rate generator settings.
+///
+/// The baud rate is `PCLK / (16 * divisor * (1 + div_add_val / mul_val))`, where `divisor` is
+/// written to DLM:DLL and `div_add_val`/`mul_val` to the fractional divider register (FDR).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BaudDivisors {
+    pub divisor: u16,
+    pub div_add_val: u8,
+    pub mul_val: u8,
+}
+
+impl BaudDivisors {
+    /// Baud rate produced by these settings from a `pclk_hz` peripheral clock.
+    #[inline]
+    pub const fn baud(&self, pclk_hz: u32) -> u32 {
+        let num = pclk_hz as u64 * self.mul_val as u64;
+        let den = 16 * self.divisor as u64 * (self.mul_val as u64 + self.div_add_val as u64);
+        (num / den) as u32
+    }
+
+    /// Find the settings giving the baud rate closest to `baud` from a `pclk_hz` peripheral
+    /// clock. Returns the settings and the absolute baud rate error.
+    pub const fn calculate(pclk_hz: u32, baud: u32) -> Option<(Self, u32)> {
+        if baud == 0 {
+            return None;
+        }
+
+        let mut best: Option<(Self, u32)> = None;
+
+        let mut mul_val = 1u8;
+        while mul_val <= 15 {
+            let mut div_add_val = 0u8;
+            while div_add_val < mul_val {
+                let num = pclk_hz as u64 * mul_val as u64;
+                let den = 16 * baud as u64 * (mul_val as u64 + div_add_val as u64);
+                let divisor = (num + den / 2) / den;
+
+                // User manual: DLM:DLL must be at least 3 when the fractional divider is used.
+                let min_divisor = if div_add_val == 0 { 1 } else { 3 };
+                if divisor >= min_divisor && divisor <= u16::MAX as u64 {
+                    let settings = Self {
+                        divisor: divisor as u16,
+                        div_add_val,
+                        mul_val,
+                    };
+                    let error = settings.baud(pclk_hz).abs_diff(baud);
+
+                    let better = match best {
+                        None => true,
+                        Some((_, best_error)) => error < best_error,
+                    };
+                    if better {
+                        best = Some((settings, error));
+                    }
+                }
+                div_add_val += 1;
+            }
+            mul_val += 1;
+        }
+
+        best
+    }
+}